@@ -0,0 +1,98 @@
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+};
+
+//Only used when storage buffers aren't supported
+let MAX_UNIFORM_LIGHTS = 64u;
+
+struct Light {
+    position: vec2<f32>;
+    radius: f32;
+    intensity: f32;
+    color: vec4<f32>;
+};
+
+//Culled and sorted on the CPU, count is how many of the lights are used
+#ifdef STORAGE_LIGHTS
+struct Lights {
+    count: u32;
+    shadows: u32;
+    //The part of the world covered by the occlusion texture
+    view_min: vec2<f32>;
+    view_max: vec2<f32>;
+    //Daylight, 0 at night
+    ambient: f32;
+    lights: array<Light>;
+};
+
+[[group(1), binding(0)]]
+var<storage, read> lights: Lights;
+#else
+struct Lights {
+    count: u32;
+    shadows: u32;
+    //The part of the world covered by the occlusion texture
+    view_min: vec2<f32>;
+    view_max: vec2<f32>;
+    //Daylight, 0 at night
+    ambient: f32;
+    lights: array<Light,MAX_UNIFORM_LIGHTS>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> lights: Lights;
+#endif
+
+[[group(1), binding(1)]]
+var occlusion_texture: texture_2d<f32>;
+[[group(1), binding(2)]]
+var occlusion_sampler: sampler;
+
+let SHADOW_STEPS = 16;
+
+fn occlusion_uv(world: vec2<f32>) -> vec2<f32> {
+    let uv = (world - lights.view_min) / (lights.view_max - lights.view_min);
+    //Texture rows go from the top of the view down
+    return vec2<f32>(uv.x, 1.0 - uv.y);
+}
+
+//How much light makes it from the light to the fragment, marching through the occlusion texture.
+//The ends are skipped so objects don't shadow themselves or the light sitting on them
+fn visibility(fragment_position: vec2<f32>, light_position: vec2<f32>) -> f32 {
+    var visible = 1.0;
+    for (var i: i32 = 1; i < SHADOW_STEPS; i = i + 1) {
+        let t = f32(i) / f32(SHADOW_STEPS);
+        let occlusion = textureSampleLevel(occlusion_texture, occlusion_sampler, occlusion_uv(mix(fragment_position, light_position, t)), 0.0).r;
+        visible = visible * (1.0 - occlusion);
+    }
+    return visible;
+}
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]]vec4<f32> {
+    var light = vec3<f32>(0.0, 0.0, 0.0);
+    for( var i: u32 = 0u; i < lights.count; i= i +1u) {
+        let source = lights.lights[i];
+        let falloff = 1.0 - smoothStep(0.0, source.radius, distance(in.world_position.xy, source.position));
+        if (falloff <= 0.0) {
+            continue;
+        }
+        var shadow = 1.0;
+        if (lights.shadows != 0u) {
+            shadow = visibility(in.world_position.xy, source.position);
+        }
+        light = light + source.color.rgb * source.intensity * falloff * shadow;
+    }
+
+    let lit = clamp(max(light.r, max(light.g, light.b)), 0.0, 1.0);
+    let brightness = clamp(lit + lights.ambient, 0.0, 1.0);
+    let darkness = 0.99 * (1.0 - brightness);
+    //A faint tint of the combined light color where it is bright, washed out by daylight
+    let glow = 0.2 * lit * (1.0 - lights.ambient);
+    let tint = light / max(lit, 0.001);
+    let alpha = darkness + glow;
+    return vec4<f32>(tint * glow / max(alpha, 0.001), alpha);
+}
@@ -0,0 +1,350 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+use serde::{Deserialize, Serialize};
+
+use crate::{prelude::*, GameState};
+
+pub struct AnimationPlugin;
+
+/// Everything that has animations in the sprite description
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub enum AnimatedSprite {
+    Player,
+    Object(WorldObject),
+}
+
+/// What an animated entity is doing, each can have its own clip.
+/// Anything without a clip plays Idle instead
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize, Inspectable,
+)]
+pub enum AnimationState {
+    Idle,
+    Walk,
+    Chop,
+    PickUp,
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize, Inspectable,
+)]
+pub enum Facing {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// A ready to play animation, frames are indices into the graphics texture atlas
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    pub frame_time: f32,
+    pub looping: bool,
+    /// Used for every direction without its own strip, drawn facing right
+    pub frames: Vec<usize>,
+    pub directions: HashMap<Facing, Vec<usize>>,
+    /// Size and anchor shared by every frame
+    pub sprite: TextureAtlasSprite,
+}
+
+/// What advancing an Animator says should be drawn
+pub struct AnimationFrame<'a> {
+    pub clip: &'a AnimationClip,
+    /// Index into the texture atlas
+    pub index: usize,
+    pub flip: bool,
+    /// Set when a different clip is drawn than last time, frames can differ in size and anchor
+    pub clip_changed: bool,
+}
+
+/// Plays the clips of an AnimatedSprite, whatever controls the entity sets the state and
+/// facing and this takes care of the frames
+#[derive(Component, Inspectable)]
+pub struct Animator {
+    #[inspectable(ignore)]
+    pub sprite: AnimatedSprite,
+    pub state: AnimationState,
+    pub facing: Facing,
+    /// Plays once over the top of state, for actions like chopping
+    one_shot: Option<AnimationState>,
+    /// The clip whose size and anchor were last put on the sprite
+    shown: Option<AnimationState>,
+    frame: usize,
+    #[inspectable(ignore)]
+    timer: Timer,
+}
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Main)
+                .with_system(Self::animate_sprites.label("animation")),
+        )
+        .register_inspectable::<Animator>();
+    }
+}
+
+impl Default for Facing {
+    fn default() -> Self {
+        Facing::Down
+    }
+}
+
+impl Facing {
+    /// The closest facing to a direction, None if there is no direction at all
+    pub fn from_direction(direction: Vec2) -> Option<Facing> {
+        if direction == Vec2::ZERO {
+            None
+        } else if direction.x.abs() >= direction.y.abs() {
+            Some(if direction.x > 0.0 {
+                Facing::Right
+            } else {
+                Facing::Left
+            })
+        } else {
+            Some(if direction.y > 0.0 {
+                Facing::Up
+            } else {
+                Facing::Down
+            })
+        }
+    }
+}
+
+impl AnimationClip {
+    /// The frames for a facing and whether they have to be flipped to face it
+    pub fn frames_for(&self, facing: Facing) -> (&[usize], bool) {
+        match self.directions.get(&facing) {
+            Some(frames) => (frames, false),
+            None => (&self.frames, facing == Facing::Left),
+        }
+    }
+}
+
+impl Animator {
+    pub fn new(sprite: AnimatedSprite) -> Self {
+        Self {
+            sprite,
+            state: AnimationState::Idle,
+            facing: Facing::default(),
+            one_shot: None,
+            shown: None,
+            frame: 0,
+            timer: Timer::from_seconds(0.0, false),
+        }
+    }
+
+    /// The state actually playing, a one shot wins over the ongoing state
+    pub fn current(&self) -> AnimationState {
+        self.one_shot.unwrap_or(self.state)
+    }
+
+    /// Switches the ongoing state, restarting only if it changed
+    pub fn set_state(&mut self, state: AnimationState) {
+        if self.state != state {
+            self.state = state;
+            if self.one_shot.is_none() {
+                self.frame = 0;
+            }
+        }
+    }
+
+    /// Plays a clip once from the start, then returns to the ongoing state
+    pub fn play_once(&mut self, state: AnimationState) {
+        self.one_shot = Some(state);
+        self.frame = 0;
+        self.timer.reset();
+    }
+
+    /// The clip for the current state, anything without a clip plays Idle
+    fn clip<'a>(
+        &self,
+        clips: &'a HashMap<AnimationState, AnimationClip>,
+    ) -> Option<(AnimationState, &'a AnimationClip)> {
+        [self.current(), AnimationState::Idle]
+            .into_iter()
+            .find_map(|state| clips.get(&state).map(|clip| (state, clip)))
+    }
+
+    /// Moves the animation on by delta and returns the frame to draw
+    pub fn advance<'a>(
+        &mut self,
+        clips: &'a HashMap<AnimationState, AnimationClip>,
+        delta: Duration,
+    ) -> Option<AnimationFrame<'a>> {
+        let (_, clip) = self.clip(clips)?;
+        let frame_count = clip.frames_for(self.facing).0.len();
+        if frame_count == 0 {
+            return None;
+        }
+
+        //Keep the timer in sync with clips of different speeds
+        if self.timer.duration().as_secs_f32() != clip.frame_time {
+            self.timer
+                .set_duration(Duration::from_secs_f32(clip.frame_time));
+        }
+        self.timer.tick(delta);
+        if self.timer.finished() {
+            self.timer.reset();
+            self.frame += 1;
+            if self.frame >= frame_count {
+                if self.one_shot.take().is_some() || clip.looping {
+                    self.frame = 0;
+                } else {
+                    self.frame = frame_count - 1;
+                }
+            }
+        }
+
+        //A one shot ending switches back to the ongoing clip, which is drawn straight away
+        let (state, clip) = self.clip(clips)?;
+        let (frames, flip) = clip.frames_for(self.facing);
+        if frames.is_empty() {
+            return None;
+        }
+        let clip_changed = self.shown != Some(state);
+        self.shown = Some(state);
+        Some(AnimationFrame {
+            clip,
+            index: frames[self.frame.min(frames.len() - 1)],
+            flip,
+            clip_changed,
+        })
+    }
+}
+
+impl AnimationPlugin {
+    fn animate_sprites(
+        time: Res<Time>,
+        graphics: Res<Graphics>,
+        mut animated: Query<(&mut Animator, &mut TextureAtlasSprite)>,
+    ) {
+        for (mut animator, mut sprite) in animated.iter_mut() {
+            let clips = match graphics.animations.get(&animator.sprite) {
+                Some(clips) => clips,
+                None => continue,
+            };
+            let frame = match animator.advance(clips, time.delta()) {
+                Some(frame) => frame,
+                None => continue,
+            };
+
+            if frame.clip_changed {
+                sprite.custom_size = frame.clip.sprite.custom_size;
+                sprite.anchor = frame.clip.sprite.anchor.clone();
+            }
+            if sprite.index != frame.index || sprite.flip_x != frame.flip {
+                sprite.index = frame.index;
+                sprite.flip_x = frame.flip;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_TIME: f32 = 0.1;
+
+    fn clip(frames: &[usize], looping: bool) -> AnimationClip {
+        AnimationClip {
+            frame_time: FRAME_TIME,
+            looping,
+            frames: frames.to_vec(),
+            directions: HashMap::default(),
+            sprite: TextureAtlasSprite::new(frames[0]),
+        }
+    }
+
+    /// Idle loops over 0 and 1, Chop plays 10 to 12 once
+    fn clips() -> HashMap<AnimationState, AnimationClip> {
+        let mut clips = HashMap::default();
+        clips.insert(AnimationState::Idle, clip(&[0, 1], true));
+        clips.insert(AnimationState::Chop, clip(&[10, 11, 12], false));
+        clips
+    }
+
+    /// Atlas indices drawn over a number of frame times
+    fn play(
+        animator: &mut Animator,
+        clips: &HashMap<AnimationState, AnimationClip>,
+        steps: usize,
+    ) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                animator
+                    .advance(clips, Duration::from_secs_f32(FRAME_TIME))
+                    .unwrap()
+                    .index
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frames_for_flips_or_uses_a_strip() {
+        let mut walk = clip(&[1, 2], true);
+        walk.directions.insert(Facing::Up, vec![5, 6]);
+
+        assert_eq!(walk.frames_for(Facing::Right), (&[1, 2][..], false));
+        assert_eq!(walk.frames_for(Facing::Down), (&[1, 2][..], false));
+        assert_eq!(walk.frames_for(Facing::Left), (&[1, 2][..], true));
+        assert_eq!(walk.frames_for(Facing::Up), (&[5, 6][..], false));
+
+        //A left strip is drawn as it is
+        walk.directions.insert(Facing::Left, vec![7]);
+        assert_eq!(walk.frames_for(Facing::Left), (&[7][..], false));
+    }
+
+    #[test]
+    fn looping_clips_wrap() {
+        let clips = clips();
+        let mut animator = Animator::new(AnimatedSprite::Player);
+        assert_eq!(play(&mut animator, &clips, 5), vec![1, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn non_looping_clips_hold_the_last_frame() {
+        let clips = clips();
+        let mut animator = Animator::new(AnimatedSprite::Player);
+        animator.set_state(AnimationState::Chop);
+        assert_eq!(play(&mut animator, &clips, 5), vec![11, 12, 12, 12, 12]);
+    }
+
+    #[test]
+    fn one_shots_return_to_the_ongoing_state() {
+        let clips = clips();
+        let mut animator = Animator::new(AnimatedSprite::Player);
+        animator.play_once(AnimationState::Chop);
+        //The tick the one shot ends already draws the idle clip
+        assert_eq!(play(&mut animator, &clips, 4), vec![11, 12, 0, 1]);
+        assert_eq!(animator.current(), AnimationState::Idle);
+    }
+
+    #[test]
+    fn missing_clips_play_idle() {
+        let clips = clips();
+        let mut animator = Animator::new(AnimatedSprite::Player);
+        animator.set_state(AnimationState::Walk);
+        assert_eq!(play(&mut animator, &clips, 2), vec![1, 0]);
+    }
+
+    #[test]
+    fn clip_changes_are_reported_once() {
+        let clips = clips();
+        let mut animator = Animator::new(AnimatedSprite::Player);
+        let delta = Duration::from_secs_f32(FRAME_TIME);
+        let mut changed = || animator.advance(&clips, delta).unwrap().clip_changed;
+        assert!(changed());
+        assert!(!changed());
+
+        animator.play_once(AnimationState::Chop);
+        let changes: Vec<bool> = (0..4)
+            .map(|_| animator.advance(&clips, delta).unwrap().clip_changed)
+            .collect();
+        assert_eq!(changes, vec![true, false, true, false]);
+    }
+}
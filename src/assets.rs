@@ -1,19 +1,103 @@
-use std::fs;
+use std::collections::BTreeMap;
 
+use bevy::asset::{AssetLoader, LoadContext, LoadState, LoadedAsset};
 use bevy::prelude::*;
-use bevy::reflect::erased_serde::private::serde::Deserialize;
+use bevy::reflect::TypeUuid;
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
 use bevy::sprite::Anchor;
-use bevy::utils::HashMap;
+use bevy::utils::{BoxedFuture, HashMap};
+use bevy_asset_loader::AssetCollection;
+use kayak_ui::font::KayakFont;
 
-use crate::item::WorldObject;
-use crate::{GameState, ImageAssets};
-use ron::de::from_str;
+use crate::animation::{AnimatedSprite, AnimationClip, AnimationState, Facing};
+use crate::crafting::CraftingBook;
+use crate::item::{ItemType, WorldObject};
+use crate::messages::Severity;
+use crate::prelude::{GameError, GameErrorType};
+use crate::GameState;
+use serde::{Deserialize, Serialize};
 
 pub struct GameAssetsPlugin;
 
+#[derive(AssetCollection)]
+pub struct ImageAssets {
+    #[asset(path = "bevy_survival_sprites.png")]
+    pub sprite_sheet: Handle<Image>,
+}
+
+#[derive(AssetCollection)]
+pub struct DataAssets {
+    #[asset(path = "bevy_survival_sprites.sprites.ron")]
+    pub sprites_desc: Handle<GraphicsDesc>,
+    /// Kept alive so edits to the recipes are hot reloaded
+    #[asset(path = "crafting.recipes.ron")]
+    pub crafting_book: Handle<CraftingBook>,
+}
+
+#[derive(AssetCollection)]
+pub struct FontAssets {
+    #[asset(path = "roboto.kayak_font")]
+    pub ui_font: Handle<KayakFont>,
+}
+
+/// Every path in the collections above, the asset loader can't report progress or failures
+/// so these are tracked separately for the splash screen
+const COLLECTION_PATHS: [&str; 4] = [
+    "bevy_survival_sprites.png",
+    "bevy_survival_sprites.sprites.ron",
+    "crafting.recipes.ron",
+    "roboto.kayak_font",
+];
+
+/// How far along loading is, shown by the splash screen.
+/// Holds the reason loading failed when the game goes to the error screen
+#[derive(Default)]
+pub struct LoadingProgress {
+    handles: Vec<(&'static str, HandleUntyped)>,
+    pub loaded: usize,
+    pub total: usize,
+    pub error: Option<String>,
+}
+
+impl LoadingProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.loaded as f32 / self.total as f32
+        }
+    }
+}
+
+/// Parses *.sprites.ron files into a GraphicsDesc
+#[derive(Default)]
+pub struct GraphicsDescLoader;
+
+impl AssetLoader for GraphicsDescLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let desc: GraphicsDesc = ron::de::from_bytes(bytes).map_err(|e| {
+                GameError::new(
+                    GameErrorType::AssetLoadFailed,
+                    format!("Failed to parse {:?}: {}", load_context.path(), e),
+                )
+            })?;
+            load_context.set_default_asset(LoadedAsset::new(desc));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sprites.ron"]
+    }
+}
+
 /// Used to describe the location and styling of sprites on the sprite sheet
-#[derive(Default, Clone, Copy, Debug, Reflect, Deserialize)]
+#[derive(Default, Clone, Copy, Debug, Reflect, Deserialize, Serialize)]
 pub struct MyRect {
     pub pos: (f32, f32),
     pub size: (f32, f32),
@@ -42,18 +126,154 @@ impl MyRect {
     }
 }
 
-/// Loaded from sprites_desc.ron and contains the description of every sprite in the game
-#[derive(Deserialize)]
+/// Frames of one animation, frames face right and are flipped to face left
+/// unless directions has a strip for that facing
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AnimationDesc {
+    pub fps: f32,
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+    pub frames: Vec<MyRect>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub directions: BTreeMap<Facing, Vec<MyRect>>,
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+/// Loaded from bevy_survival_sprites.sprites.ron and contains the description of every sprite
+/// in the game, written by the pack_sprites tool
+#[derive(Deserialize, Serialize, TypeUuid)]
+#[uuid = "5b0f2c1d-8e3a-4f6b-9c7d-2a4e6f8b1d35"]
 pub struct GraphicsDesc {
-    map: HashMap<WorldObject, MyRect>,
+    pub map: BTreeMap<WorldObject, MyRect>,
+    /// Drawn for any object missing from map, defaults to the Item(None) graphic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<MyRect>,
+    /// Background of inventory and crafting slots
+    #[serde(default = "default_ui_box")]
+    pub ui_box: MyRect,
+    /// Shown next to each message in the message log
+    #[serde(default = "default_message_icons")]
+    pub message_icons: BTreeMap<Severity, MyRect>,
+    #[serde(default)]
+    pub animations: BTreeMap<AnimatedSprite, BTreeMap<AnimationState, AnimationDesc>>,
+}
+
+fn default_ui_box() -> MyRect {
+    MyRect::new((0., 32.), (32., 32.))
+}
+
+fn default_message_icons() -> BTreeMap<Severity, MyRect> {
+    BTreeMap::from([
+        (Severity::Info, MyRect::new((208., 64.), (16., 16.))),
+        (Severity::Warning, MyRect::new((224., 64.), (16., 16.))),
+        (Severity::Error, MyRect::new((240., 64.), (16., 16.))),
+    ])
+}
+
+impl Default for GraphicsDesc {
+    fn default() -> Self {
+        Self {
+            map: BTreeMap::new(),
+            placeholder: None,
+            ui_box: default_ui_box(),
+            message_icons: default_message_icons(),
+            animations: BTreeMap::new(),
+        }
+    }
+}
+
+impl GraphicsDesc {
+    pub fn placeholder(&self) -> Option<MyRect> {
+        self.placeholder
+            .or_else(|| self.map.get(&WorldObject::Item(ItemType::None)).copied())
+    }
+
+    /// Everything wrong with the description, missing objects are drawn with the placeholder
+    /// so none of these stop the game
+    pub fn validate(&self, sheet_size: Vec2) -> Vec<GameError> {
+        let mut errors = Vec::new();
+        let mut check_rect = |name: String, rect: &MyRect| {
+            if rect.size.0 <= 0.0 || rect.size.1 <= 0.0 {
+                errors.push(GameError::new(
+                    GameErrorType::InvalidGraphics,
+                    format!("{} has an empty size {:?}", name, rect.size),
+                ));
+            }
+            if rect.pos.0 < 0.0
+                || rect.pos.1 < 0.0
+                || rect.pos.0 + rect.size.0 > sheet_size.x
+                || rect.pos.1 + rect.size.1 > sheet_size.y
+            {
+                errors.push(GameError::new(
+                    GameErrorType::InvalidGraphics,
+                    format!("{} at {:?} is outside the sprite sheet", name, rect.pos),
+                ));
+            }
+        };
+
+        for (object, rect) in self.map.iter() {
+            check_rect(format!("{:?}", object), rect);
+        }
+        if let Some(placeholder) = self.placeholder.as_ref() {
+            check_rect("Placeholder".to_string(), placeholder);
+        }
+        check_rect("UI box".to_string(), &self.ui_box);
+        for (severity, rect) in self.message_icons.iter() {
+            check_rect(format!("{:?} message icon", severity), rect);
+        }
+        for (animated, clips) in self.animations.iter() {
+            for (state, clip) in clips.iter() {
+                let name = format!("{:?} {:?}", animated, state);
+                if clip.frames.is_empty() {
+                    errors.push(GameError::new(
+                        GameErrorType::InvalidGraphics,
+                        format!("{} has no frames", name),
+                    ));
+                }
+                if clip.fps <= 0.0 {
+                    errors.push(GameError::new(
+                        GameErrorType::InvalidGraphics,
+                        format!("{} has fps {}", name, clip.fps),
+                    ));
+                }
+                for rect in clip.frames.iter().chain(clip.directions.values().flatten()) {
+                    check_rect(name.clone(), rect);
+                }
+            }
+        }
+
+        for object in WorldObject::all() {
+            if !self.map.contains_key(&object) {
+                errors.push(GameError::new(
+                    GameErrorType::InvalidGraphics,
+                    format!("No graphic for object {:?}, using the placeholder", object),
+                ));
+            }
+        }
+        if self.placeholder().is_none() {
+            errors.push(GameError::new(
+                GameErrorType::InvalidGraphics,
+                "No placeholder graphic and no Item(None) graphic to use instead".to_string(),
+            ));
+        }
+        errors
+    }
 }
 
 impl Plugin for GameAssetsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_exit(GameState::Splash)
-                .with_system(Self::load_graphics.label("graphics")),
-        );
+        app.add_asset::<GraphicsDesc>()
+            .init_asset_loader::<GraphicsDescLoader>()
+            .init_resource::<LoadingProgress>()
+            .add_system_set(SystemSet::on_enter(GameState::Splash).with_system(Self::track_loading))
+            .add_system_set(
+                SystemSet::on_update(GameState::Splash)
+                    .with_system(Self::update_loading_progress)
+                    .with_system(Self::finish_loading.after(Self::update_loading_progress)),
+            );
     }
 }
 
@@ -65,114 +285,441 @@ pub const TILE_SIZE: f32 = SOURCE_TILE_SIZE * PIXEL_SCALE;
 /// Contains an image map which is the work around for UI not supporting texture atlas sprites
 pub struct Graphics {
     pub texture_atlas: Handle<TextureAtlas>,
-    pub player_index: usize,
     pub box_index: usize,
+    /// Background drawn behind every item slot in the UI
+    pub box_image: Handle<Image>,
+    /// Every severity has one, missing icons fall back to the placeholder
+    pub message_icons: HashMap<Severity, Handle<Image>>,
     pub item_map: HashMap<WorldObject, TextureAtlasSprite>,
     pub image_map: HashMap<WorldObject, Handle<Image>>,
+    pub animations: HashMap<AnimatedSprite, HashMap<AnimationState, AnimationClip>>,
+}
+
+impl Graphics {
+    /// The first idle frame of an animated sprite, what it looks like when spawned
+    pub fn animation_sprite(&self, sprite: AnimatedSprite) -> Option<TextureAtlasSprite> {
+        let clip = self.animations.get(&sprite)?.get(&AnimationState::Idle)?;
+        let mut atlas_sprite = clip.sprite.clone();
+        atlas_sprite.index = *clip.frames.first()?;
+        Some(atlas_sprite)
+    }
+}
+
+/// Adds a rectangle to the atlas and creates a sprite sized and anchored to match it
+fn add_sprite(atlas: &mut TextureAtlas, rect: &MyRect) -> TextureAtlasSprite {
+    let mut sprite = TextureAtlasSprite::new(atlas.add_texture(rect.to_atlas_rect()));
+
+    //Set the size to be proportional to the source rectangle
+    sprite.custom_size = Some(Vec2::new(
+        rect.size.0 / SOURCE_TILE_SIZE,
+        rect.size.1 / SOURCE_TILE_SIZE,
+    ));
+
+    //Position the sprite anchor if one is defined
+    if let Some(anchor) = rect.anchor {
+        sprite.anchor = Anchor::Custom(Vec2::new(
+            anchor.0 / rect.size.0 - 0.5,
+            0.5 - anchor.1 / rect.size.1,
+        ));
+    };
+    sprite
+}
+
+/// Like convert_to_image but always gives an image, a transparent one when the sprite is
+/// broken, so every object has something to show in the UI
+fn ui_image(rect: MyRect, sheet: Handle<Image>, assets: &mut Assets<Image>) -> Handle<Image> {
+    convert_to_image(rect, sheet, assets).unwrap_or_else(|e| {
+        error!("{}", e);
+        assets.add(Image::new_fill(
+            Extent3d {
+                width: rect.size.0 as u32,
+                height: rect.size.1 as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 0],
+            TextureFormat::Rgba8UnormSrgb,
+        ))
+    })
 }
 
-/// Work around helper function to convert texture atlas sprites into stand alone image handles
-/// Copies sprite data pixel by pixel, needed to render things in UI
+/// Work around helper function to convert texture atlas sprites into stand alone image handles,
+/// needed to render things in UI
 fn convert_to_image(
     sprite_desc: MyRect,
     original_image: Handle<Image>,
-    assets: &mut ResMut<Assets<Image>>,
-) -> Handle<Image> {
-    //TODO convert if mismatch
-    let original_image = assets.get(original_image).unwrap();
-    assert!(original_image.texture_descriptor.format == TextureFormat::Rgba8UnormSrgb);
-
-    let mut data = Vec::default();
-    //Every pixel is 4 entries in image.data
-    let mut starting_index =
-        (sprite_desc.pos.0 + original_image.size().x * sprite_desc.pos.1) as usize;
-    for _y in 0..sprite_desc.size.1 as usize {
-        for x in 0..sprite_desc.size.0 as usize {
-            let index = starting_index + x;
-            //Copy 1 pixel at index
-            data.push(original_image.data[index * 4]);
-            data.push(original_image.data[index * 4 + 1]);
-            data.push(original_image.data[index * 4 + 2]);
-            data.push(original_image.data[index * 4 + 3]);
-        }
-        starting_index += original_image.size().y as usize;
+    assets: &mut Assets<Image>,
+) -> Result<Handle<Image>, GameError> {
+    let original_image = assets.get(original_image).ok_or_else(|| {
+        GameError::new(
+            GameErrorType::AssetLoadFailed,
+            "Sprite sheet is not loaded".to_string(),
+        )
+    })?;
+    let image = slice_image(original_image, sprite_desc)?;
+    Ok(assets.add(image))
+}
+
+/// Copies the pixels under a rectangle into a new image of the same format, row by row.
+/// Block compressed images are converted to Rgba8UnormSrgb first as they can't be sliced
+/// on pixel boundaries
+pub fn slice_image(original_image: &Image, sprite_desc: MyRect) -> Result<Image, GameError> {
+    let format_info = original_image.texture_descriptor.format.describe();
+    if format_info.block_dimensions != (1, 1) {
+        let converted = original_image
+            .convert(TextureFormat::Rgba8UnormSrgb)
+            .ok_or_else(|| {
+                GameError::new(
+                    GameErrorType::AssetLoadFailed,
+                    format!(
+                        "Can't slice sprites from a {:?} image",
+                        original_image.texture_descriptor.format
+                    ),
+                )
+            })?;
+        return slice_image(&converted, sprite_desc);
+    }
+
+    let bytes_per_pixel = format_info.block_size as usize;
+    let image_width = original_image.texture_descriptor.size.width as usize;
+    let image_height = original_image.texture_descriptor.size.height as usize;
+    let (x, y) = (sprite_desc.pos.0 as usize, sprite_desc.pos.1 as usize);
+    let (width, height) = (sprite_desc.size.0 as usize, sprite_desc.size.1 as usize);
+
+    if x + width > image_width || y + height > image_height {
+        return Err(GameError::new(
+            GameErrorType::AssetLoadFailed,
+            format!(
+                "Sprite {:?} is outside of the {}x{} sprite sheet",
+                sprite_desc, image_width, image_height
+            ),
+        ));
+    }
+
+    if original_image.data.len() < image_width * image_height * bytes_per_pixel {
+        return Err(GameError::new(
+            GameErrorType::AssetLoadFailed,
+            format!(
+                "Sprite sheet has {} bytes, too few for {}x{} pixels",
+                original_image.data.len(),
+                image_width,
+                image_height
+            ),
+        ));
+    }
+
+    let row_length = width * bytes_per_pixel;
+    let mut data = Vec::with_capacity(row_length * height);
+    for row in y..y + height {
+        let start = (row * image_width + x) * bytes_per_pixel;
+        data.extend_from_slice(&original_image.data[start..start + row_length]);
     }
 
     let size = Extent3d {
-        width: sprite_desc.size.0 as u32,
-        height: sprite_desc.size.1 as u32,
+        width: width as u32,
+        height: height as u32,
         depth_or_array_layers: 1,
     };
-    let image = Image::new(
+    Ok(Image::new(
         size,
         TextureDimension::D2,
         data,
-        //FIXME
-        TextureFormat::Rgba8UnormSrgb,
-    );
-    assets.add(image)
+        original_image.texture_descriptor.format,
+    ))
 }
 
 impl GameAssetsPlugin {
-    /// Startup system that runs after images are loaded, indexes all loaded images
-    /// and creates the graphics resource
-    fn load_graphics(
+    fn track_loading(asset_server: Res<AssetServer>, mut progress: ResMut<LoadingProgress>) {
+        //Loading a path again gives the same handle the asset loader is waiting on
+        progress.handles = COLLECTION_PATHS
+            .iter()
+            .map(|path| (*path, asset_server.load_untyped(*path)))
+            .collect();
+        progress.total = progress.handles.len();
+    }
+
+    fn update_loading_progress(
+        asset_server: Res<AssetServer>,
+        mut progress: ResMut<LoadingProgress>,
+        mut state: ResMut<State<GameState>>,
+    ) {
+        let mut loaded = 0;
+        for (path, handle) in progress.handles.iter() {
+            match asset_server.get_load_state(handle) {
+                LoadState::Loaded => loaded += 1,
+                LoadState::Failed => {
+                    let error = GameError::new(
+                        GameErrorType::AssetLoadFailed,
+                        format!("Failed to load {}, see the log for details", path),
+                    );
+                    error!("{}", error);
+                    progress.error = Some(error.to_string());
+                    state.set(GameState::LoadError).unwrap();
+                    return;
+                }
+                _ => {}
+            }
+        }
+        if progress.loaded != loaded {
+            progress.loaded = loaded;
+        }
+    }
+
+    /// Once every collection is in place builds the graphics and moves on to the game
+    fn finish_loading(
         mut commands: Commands,
+        collections: (
+            Option<Res<ImageAssets>>,
+            Option<Res<DataAssets>>,
+            Option<Res<FontAssets>>,
+        ),
+        desc_assets: Res<Assets<GraphicsDesc>>,
         mut image_assets: ResMut<Assets<Image>>,
-        sprite_sheet: Res<ImageAssets>,
         mut texture_assets: ResMut<Assets<TextureAtlas>>,
+        mut progress: ResMut<LoadingProgress>,
+        mut state: ResMut<State<GameState>>,
     ) {
-        //let image_handle = assets.load("bevy_survival_sprites.png");
-        let image_handle = sprite_sheet.sprite_sheet.clone();
-        let sprite_desc = fs::read_to_string("assets/sprites_desc.ron").unwrap();
+        let (sprite_sheet, data_assets) = match collections {
+            (Some(sprite_sheet), Some(data_assets), Some(_)) => (sprite_sheet, data_assets),
+            _ => return,
+        };
+        if progress.error.is_some() {
+            return;
+        }
 
-        let sprite_desc: GraphicsDesc = from_str(&sprite_desc).unwrap_or_else(|e| {
-            println!("Failed to load config for graphics: {}", e);
-            std::process::exit(1);
-        });
+        let graphics = desc_assets
+            .get(&data_assets.sprites_desc)
+            .ok_or_else(|| {
+                GameError::new(
+                    GameErrorType::AssetLoadFailed,
+                    "Sprite description is not loaded".to_string(),
+                )
+            })
+            .and_then(|desc| {
+                Self::load_graphics(
+                    desc,
+                    sprite_sheet.sprite_sheet.clone(),
+                    &mut image_assets,
+                    &mut texture_assets,
+                )
+            });
 
-        let mut atlas = TextureAtlas::new_empty(image_handle.clone(), Vec2::splat(256.0));
+        match graphics {
+            Ok(graphics) => {
+                commands.insert_resource(graphics);
+                state.set(GameState::MainMenu).unwrap();
+            }
+            Err(e) => {
+                error!("{}", e);
+                progress.error = Some(e.to_string());
+                state.set(GameState::LoadError).unwrap();
+            }
+        }
+    }
 
-        let player_index = atlas.add_texture(MyRect::new((0., 0.), (32., 32.)).to_atlas_rect());
+    /// Indexes every sprite in the description and creates the graphics resource
+    fn load_graphics(
+        sprite_desc: &GraphicsDesc,
+        image_handle: Handle<Image>,
+        image_assets: &mut Assets<Image>,
+        texture_assets: &mut Assets<TextureAtlas>,
+    ) -> Result<Graphics, GameError> {
+        let sheet_size = image_assets
+            .get(&image_handle)
+            .map(|image| image.size())
+            .unwrap_or_else(|| Vec2::splat(256.0));
+        for error in sprite_desc.validate(sheet_size) {
+            warn!("{}", error);
+        }
+        let placeholder = sprite_desc.placeholder().ok_or_else(|| {
+            GameError::new(
+                GameErrorType::InvalidGraphics,
+                "No placeholder graphic for missing sprites".to_string(),
+            )
+        })?;
+
+        let mut atlas = TextureAtlas::new_empty(image_handle.clone(), sheet_size);
 
         let mut item_map = HashMap::default();
         let mut image_map = HashMap::default();
 
-        for (item, rect) in sprite_desc.map.iter() {
-            println!("Found graphic {:?}", item);
-            let mut sprite = TextureAtlasSprite::new(atlas.add_texture(rect.to_atlas_rect()));
+        //Anything missing from the description is drawn with the placeholder
+        let graphics_desc = WorldObject::all()
+            .into_iter()
+            .map(|object| {
+                let rect = sprite_desc.map.get(&object).copied().unwrap_or(placeholder);
+                (object, rect)
+            })
+            .chain(
+                sprite_desc
+                    .map
+                    .iter()
+                    .map(|(object, rect)| (*object, *rect)),
+            )
+            .collect::<BTreeMap<_, _>>();
 
-            //Set the size to be proportional to the source rectangle
-            sprite.custom_size = Some(Vec2::new(
-                rect.size.0 / SOURCE_TILE_SIZE,
-                rect.size.1 / SOURCE_TILE_SIZE,
-            ));
-
-            //Position the sprite anchor if one is defined
-            if let Some(anchor) = rect.anchor {
-                sprite.anchor = Anchor::Custom(Vec2::new(
-                    anchor.0 / rect.size.0 - 0.5,
-                    0.5 - anchor.1 / rect.size.1,
-                ));
-            };
+        for (item, rect) in graphics_desc.iter() {
+            println!("Found graphic {:?}", item);
+            let sprite = add_sprite(&mut atlas, rect);
 
             item_map.insert(*item, sprite);
-            image_map.insert(
-                *item,
-                convert_to_image(*rect, image_handle.clone(), &mut image_assets),
-            );
+            image_map.insert(*item, ui_image(*rect, image_handle.clone(), image_assets));
+        }
+
+        let mut animations = HashMap::default();
+        for (animated, clip_descs) in sprite_desc.animations.iter() {
+            let mut clips = HashMap::default();
+            for (state, desc) in clip_descs.iter() {
+                let first = match desc.frames.first() {
+                    Some(first) => first,
+                    //Already reported by validate
+                    None => continue,
+                };
+                let sprite = add_sprite(&mut atlas, first);
+                let mut frames = vec![sprite.index];
+                frames.extend(
+                    desc.frames[1..]
+                        .iter()
+                        .map(|rect| atlas.add_texture(rect.to_atlas_rect())),
+                );
+                let directions = desc
+                    .directions
+                    .iter()
+                    .map(|(facing, rects)| {
+                        let indices = rects
+                            .iter()
+                            .map(|rect| atlas.add_texture(rect.to_atlas_rect()))
+                            .collect();
+                        (*facing, indices)
+                    })
+                    .collect();
+                clips.insert(
+                    *state,
+                    AnimationClip {
+                        frame_time: 1.0 / desc.fps.max(0.001),
+                        looping: desc.looping,
+                        frames,
+                        directions,
+                        sprite,
+                    },
+                );
+            }
+            animations.insert(*animated, clips);
         }
 
-        let box_index = atlas.add_texture(MyRect::new((0., 32.), (32., 32.)).to_atlas_rect());
+        let box_index = atlas.add_texture(sprite_desc.ui_box.to_atlas_rect());
+        let box_image = ui_image(sprite_desc.ui_box, image_handle.clone(), image_assets);
+        let message_icons = [Severity::Info, Severity::Warning, Severity::Error]
+            .into_iter()
+            .map(|severity| {
+                let rect = sprite_desc
+                    .message_icons
+                    .get(&severity)
+                    .copied()
+                    .or_else(|| sprite_desc.placeholder())
+                    .unwrap_or(sprite_desc.ui_box);
+                (severity, ui_image(rect, image_handle.clone(), image_assets))
+            })
+            .collect();
 
         let atlas_handle = texture_assets.add(atlas);
 
-        commands.insert_resource(Graphics {
+        Ok(Graphics {
             texture_atlas: atlas_handle,
-            player_index,
             box_index,
+            box_image,
+            message_icons,
             item_map,
             image_map,
-        });
+            animations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every pixel holds its own coordinates so a wrong stride or offset shows up in the copy
+    fn test_image(width: u32, height: u32, format: TextureFormat) -> Image {
+        let bytes_per_pixel = format.describe().block_size as usize;
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let mut pixel = vec![0; bytes_per_pixel];
+                pixel[0] = x as u8;
+                pixel[1] = y as u8;
+                pixel[bytes_per_pixel - 1] = 0xff;
+                data.extend(pixel);
+            }
+        }
+        Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            format,
+        )
+    }
+
+    fn check_slice(format: TextureFormat) {
+        let image = test_image(8, 6, format);
+        let bytes_per_pixel = format.describe().block_size as usize;
+        let rect = MyRect::new((2., 1.), (3., 4.));
+
+        let sliced = slice_image(&image, rect).expect("rect is inside the image");
+        assert_eq!(sliced.texture_descriptor.format, format);
+        assert_eq!(sliced.texture_descriptor.size.width, 3);
+        assert_eq!(sliced.texture_descriptor.size.height, 4);
+        assert_eq!(sliced.data.len(), 3 * 4 * bytes_per_pixel);
+
+        for (row, bytes) in sliced.data.chunks(3 * bytes_per_pixel).enumerate() {
+            for (column, pixel) in bytes.chunks(bytes_per_pixel).enumerate() {
+                assert_eq!(pixel[0] as usize, 2 + column, "x of row {}", row);
+                assert_eq!(pixel[1] as usize, 1 + row, "y of row {}", row);
+                assert_eq!(pixel[bytes_per_pixel - 1], 0xff);
+            }
+        }
+    }
+
+    #[test]
+    fn slices_rgba8_rows() {
+        check_slice(TextureFormat::Rgba8UnormSrgb);
+    }
+
+    #[test]
+    fn slices_multi_byte_formats() {
+        check_slice(TextureFormat::Rgba16Uint);
+        check_slice(TextureFormat::Rgba32Float);
+    }
+
+    #[test]
+    fn whole_image_slice_is_a_copy() {
+        let image = test_image(8, 6, TextureFormat::Rgba8UnormSrgb);
+        let sliced = slice_image(&image, MyRect::new((0., 0.), (8., 6.))).unwrap();
+        assert_eq!(sliced.data, image.data);
+    }
+
+    #[test]
+    fn out_of_bounds_rects_are_errors() {
+        let image = test_image(8, 6, TextureFormat::Rgba8UnormSrgb);
+        for rect in [
+            MyRect::new((6., 0.), (3., 2.)),
+            MyRect::new((0., 5.), (2., 2.)),
+            MyRect::new((0., 0.), (9., 6.)),
+            MyRect::new((100., 100.), (1., 1.)),
+        ] {
+            assert!(slice_image(&image, rect).is_err(), "{:?}", rect);
+        }
+    }
+
+    #[test]
+    fn truncated_data_is_an_error() {
+        let mut image = test_image(8, 6, TextureFormat::Rgba8UnormSrgb);
+        image.data.truncate(10);
+        assert!(slice_image(&image, MyRect::new((0., 0.), (2., 2.))).is_err());
     }
 }
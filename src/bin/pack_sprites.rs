@@ -0,0 +1,358 @@
+//! Packs a folder of individual PNGs into the sprite sheet and writes the matching description
+//!
+//! cargo run --bin pack_sprites -- <folder> [--width 256] [--sheet <png>] [--desc <ron>] [--force]
+//!
+//! The sheet and description in assets/ are built from sprites/, existing files are only
+//! overwritten with --force so edit the source frames rather than the packed sheet
+//!
+//! The folder is laid out as
+//!   Tree.png, Flint.png, Item(Tool(Axe)).png   one sprite per world object
+//!   placeholder.png                            optional, drawn for missing objects
+//!   box.png                                    background of inventory slots
+//!   icons/Info.png, Warning.png, Error.png     message log icons
+//!   animations/Player/Walk/0.png, 1.png ...    animation frames in numeric order, 2 before 10
+//!   animations/Player/Walk/Up/0.png ...        optional strip for a single facing
+//!   pack.ron                                   optional anchors and animation speeds
+//!
+//! pack.ron looks like
+//!   (anchors: { "Tree": (16., 44.), "Player": (16., 30.) }, fps: { "Player/Walk": 8. },
+//!    once: ["Player/Chop"])
+//! Objects without an anchor are anchored at their bottom centre, items are centred
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+use image::{GenericImage, RgbaImage};
+use ron::ser::PrettyConfig;
+use serde::Deserialize;
+
+use bevy_survival_crafting_game::{
+    animation::{AnimatedSprite, AnimationState, Facing},
+    assets::{AnimationDesc, GraphicsDesc, MyRect},
+    item::{ItemType, Tool, WorldObject},
+    messages::Severity,
+    sprite_packer::{natural_cmp, SpritePacker},
+};
+
+const DEFAULT_SHEET: &str = "assets/bevy_survival_sprites.png";
+const DEFAULT_DESC: &str = "assets/bevy_survival_sprites.sprites.ron";
+const DEFAULT_FPS: f32 = 8.0;
+
+#[derive(Default, Deserialize)]
+struct PackSettings {
+    #[serde(default)]
+    anchors: HashMap<String, (f32, f32)>,
+    #[serde(default)]
+    fps: HashMap<String, f32>,
+    /// Animations which play once instead of looping
+    #[serde(default)]
+    once: Vec<String>,
+}
+
+/// Where a loaded image ends up in the description
+enum SpriteTarget {
+    Object(WorldObject),
+    Placeholder,
+    UiBox,
+    MessageIcon(Severity),
+    Frame {
+        sprite: AnimatedSprite,
+        state: AnimationState,
+        facing: Option<Facing>,
+    },
+}
+
+struct Sprite {
+    target: SpriteTarget,
+    /// Key into PackSettings anchors
+    anchor_key: String,
+    image: RgbaImage,
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: pack_sprites <folder> [--width <pixels>] [--sheet <png>] [--desc <ron>] [--force]"
+    );
+    process::exit(2);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn parse_object(name: &str) -> Option<WorldObject> {
+    ron::de::from_str::<WorldObject>(name)
+        .or_else(|_| ron::de::from_str::<ItemType>(name).map(WorldObject::Item))
+        .or_else(|_| {
+            ron::de::from_str::<Tool>(name).map(|tool| WorldObject::Item(ItemType::Tool(tool)))
+        })
+        .ok()
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Every entry in a folder sorted by name so packing is repeatable, numbers in names
+/// are compared by value so frames don't need zero padding
+fn sorted_entries(folder: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(folder)
+        .unwrap_or_else(|e| fail(format!("Failed to read {}: {}", folder.display(), e)))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    let file_name = |path: &PathBuf| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    entries.sort_by(|a, b| natural_cmp(&file_name(a), &file_name(b)));
+    entries
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension().map(|ext| ext == "png").unwrap_or(false)
+}
+
+fn load_image(path: &Path) -> RgbaImage {
+    image::open(path)
+        .unwrap_or_else(|e| fail(format!("Failed to load {}: {}", path.display(), e)))
+        .to_rgba8()
+}
+
+fn load_frames(
+    folder: &Path,
+    sprite: AnimatedSprite,
+    state: AnimationState,
+    facing: Option<Facing>,
+    anchor_key: &str,
+    sprites: &mut Vec<Sprite>,
+) {
+    for path in sorted_entries(folder) {
+        if path.is_dir() && facing.is_none() {
+            match ron::de::from_str::<Facing>(&file_stem(&path)) {
+                Ok(facing) => load_frames(&path, sprite, state, Some(facing), anchor_key, sprites),
+                Err(_) => eprintln!("Skipping {}, not a facing", path.display()),
+            }
+        } else if is_png(&path) {
+            sprites.push(Sprite {
+                target: SpriteTarget::Frame {
+                    sprite,
+                    state,
+                    facing,
+                },
+                anchor_key: anchor_key.to_string(),
+                image: load_image(&path),
+            });
+        }
+    }
+}
+
+fn load_sprites(folder: &Path) -> Vec<Sprite> {
+    let mut sprites = Vec::new();
+    for path in sorted_entries(folder) {
+        let name = file_stem(&path);
+        if path.is_dir() && name == "animations" {
+            for sprite_folder in sorted_entries(&path).into_iter().filter(|p| p.is_dir()) {
+                let sprite_name = file_stem(&sprite_folder);
+                let sprite = ron::de::from_str::<AnimatedSprite>(&sprite_name)
+                    .ok()
+                    .or_else(|| parse_object(&sprite_name).map(AnimatedSprite::Object))
+                    .unwrap_or_else(|| fail(format!("Unknown animated sprite {}", sprite_name)));
+                for state_folder in sorted_entries(&sprite_folder)
+                    .into_iter()
+                    .filter(|p| p.is_dir())
+                {
+                    let state_name = file_stem(&state_folder);
+                    let state = ron::de::from_str::<AnimationState>(&state_name)
+                        .unwrap_or_else(|_| fail(format!("Unknown animation {}", state_name)));
+                    load_frames(
+                        &state_folder,
+                        sprite,
+                        state,
+                        None,
+                        &sprite_name,
+                        &mut sprites,
+                    );
+                }
+            }
+        } else if path.is_dir() && name == "icons" {
+            for icon in sorted_entries(&path).into_iter().filter(|p| is_png(p)) {
+                let icon_name = file_stem(&icon);
+                match ron::de::from_str::<Severity>(&icon_name) {
+                    Ok(severity) => sprites.push(Sprite {
+                        target: SpriteTarget::MessageIcon(severity),
+                        anchor_key: icon_name,
+                        image: load_image(&icon),
+                    }),
+                    Err(_) => eprintln!("Skipping {}, not a severity", icon.display()),
+                }
+            }
+        } else if is_png(&path) {
+            let target = if name == "placeholder" {
+                SpriteTarget::Placeholder
+            } else if name == "box" {
+                SpriteTarget::UiBox
+            } else {
+                match parse_object(&name) {
+                    Some(object) => SpriteTarget::Object(object),
+                    None => {
+                        eprintln!("Skipping {}, not a world object", path.display());
+                        continue;
+                    }
+                }
+            };
+            sprites.push(Sprite {
+                target,
+                anchor_key: name,
+                image: load_image(&path),
+            });
+        }
+    }
+    sprites
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let folder = PathBuf::from(args.next().unwrap_or_else(|| usage()));
+    let mut width = 256;
+    let mut sheet_path = DEFAULT_SHEET.to_string();
+    let mut desc_path = DEFAULT_DESC.to_string();
+    let mut force = false;
+    while let Some(flag) = args.next() {
+        if flag == "--force" {
+            force = true;
+            continue;
+        }
+        let value = args.next().unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "--width" => width = value.parse().unwrap_or_else(|_| usage()),
+            "--sheet" => sheet_path = value,
+            "--desc" => desc_path = value,
+            _ => usage(),
+        }
+    }
+
+    //Checked before packing so nothing is half written
+    if !force {
+        for path in [&sheet_path, &desc_path] {
+            if Path::new(path).exists() {
+                fail(format!(
+                    "{} already exists, pass --force to overwrite it",
+                    path
+                ));
+            }
+        }
+    }
+
+    let settings_path = folder.join("pack.ron");
+    let settings: PackSettings = if settings_path.exists() {
+        let settings = fs::read_to_string(&settings_path)
+            .unwrap_or_else(|e| fail(format!("Failed to read {}: {}", settings_path.display(), e)));
+        ron::de::from_str(&settings).unwrap_or_else(|e| {
+            fail(format!(
+                "Failed to parse {}: {}",
+                settings_path.display(),
+                e
+            ))
+        })
+    } else {
+        PackSettings::default()
+    };
+
+    let sprites = load_sprites(&folder);
+    let sizes: Vec<(u32, u32)> = sprites
+        .iter()
+        .map(|sprite| sprite.image.dimensions())
+        .collect();
+    let packed = SpritePacker { width, padding: 1 }
+        .pack(&sizes)
+        .unwrap_or_else(|e| fail(e.to_string()));
+
+    let mut sheet = RgbaImage::new(width, packed.height);
+    let mut desc = GraphicsDesc::default();
+    //The default icons point into the old sheet, missing ones use the placeholder instead
+    desc.message_icons.clear();
+    for (sprite, (x, y)) in sprites.iter().zip(packed.positions.iter()) {
+        sheet
+            .copy_from(&sprite.image, *x, *y)
+            .unwrap_or_else(|e| fail(e.to_string()));
+
+        let (w, h) = sprite.image.dimensions();
+        let is_item = matches!(sprite.target, SpriteTarget::Object(WorldObject::Item(_)))
+            || matches!(
+                sprite.target,
+                SpriteTarget::Placeholder | SpriteTarget::UiBox | SpriteTarget::MessageIcon(_)
+            );
+        let anchor = settings
+            .anchors
+            .get(&sprite.anchor_key)
+            .copied()
+            .or(if is_item {
+                None
+            } else {
+                Some((w as f32 / 2.0, h as f32 - 2.0))
+            });
+        let rect = MyRect {
+            pos: (*x as f32, *y as f32),
+            size: (w as f32, h as f32),
+            anchor,
+        };
+
+        match sprite.target {
+            SpriteTarget::Object(object) => {
+                desc.map.insert(object, rect);
+            }
+            SpriteTarget::Placeholder => desc.placeholder = Some(rect),
+            SpriteTarget::UiBox => desc.ui_box = rect,
+            SpriteTarget::MessageIcon(severity) => {
+                desc.message_icons.insert(severity, rect);
+            }
+            SpriteTarget::Frame {
+                sprite: animated,
+                state,
+                facing,
+            } => {
+                let key = format!("{}/{:?}", sprite.anchor_key, state);
+                let clip = desc
+                    .animations
+                    .entry(animated)
+                    .or_insert_with(BTreeMap::new)
+                    .entry(state)
+                    .or_insert_with(|| AnimationDesc {
+                        fps: settings.fps.get(&key).copied().unwrap_or(DEFAULT_FPS),
+                        looping: !settings.once.contains(&key),
+                        frames: Vec::new(),
+                        directions: BTreeMap::new(),
+                    });
+                match facing {
+                    Some(facing) => clip.directions.entry(facing).or_default().push(rect),
+                    None => clip.frames.push(rect),
+                }
+            }
+        }
+    }
+
+    let sheet_size = bevy::math::Vec2::new(width as f32, packed.height as f32);
+    for error in desc.validate(sheet_size) {
+        eprintln!("{}", error);
+    }
+
+    sheet
+        .save(&sheet_path)
+        .unwrap_or_else(|e| fail(format!("Failed to write {}: {}", sheet_path, e)));
+    let desc_text = ron::ser::to_string_pretty(&desc, PrettyConfig::default())
+        .unwrap_or_else(|e| fail(e.to_string()));
+    fs::write(&desc_path, desc_text)
+        .unwrap_or_else(|e| fail(format!("Failed to write {}: {}", desc_path, e)));
+    println!(
+        "Packed {} sprites into {} ({}x{}) and {}",
+        sprites.len(),
+        sheet_path,
+        width,
+        packed.height,
+        desc_path
+    );
+}
@@ -0,0 +1,112 @@
+//! Command line tool for reasoning about the recipe book without starting the game
+//!
+//! cargo run --bin recipe_graph -- [--book <path>] <command>
+//!   tree <item>       print everything needed to craft an item
+//!   cost <item>       total raw resources needed to craft an item
+//!   check             report cycles, unreachable and unused items
+//!   dot [file]        export the recipe graph in Graphviz DOT format
+//!
+//! Items use the same syntax as the recipe file, for example CampFire or Item(Tool(Axe)),
+//! plain item names like Axe are also accepted
+use std::{env, fs, process};
+
+use bevy_survival_crafting_game::{
+    crafting::CraftingBook,
+    crafting_graph::RecipeGraph,
+    item::{ItemType, Tool, WorldObject},
+};
+
+const DEFAULT_BOOK: &str = "assets/crafting.recipes.ron";
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: recipe_graph [--book <path>] <tree <item> | cost <item> | check | dot [file]>"
+    );
+    process::exit(2);
+}
+
+fn parse_object(arg: &str) -> WorldObject {
+    ron::de::from_str::<WorldObject>(arg)
+        .or_else(|_| ron::de::from_str::<ItemType>(arg).map(WorldObject::Item))
+        .or_else(|_| {
+            ron::de::from_str::<Tool>(arg).map(|tool| WorldObject::Item(ItemType::Tool(tool)))
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("Unknown item {}: {}", arg, e);
+            process::exit(2);
+        })
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut book_path = DEFAULT_BOOK.to_string();
+    if args.first().map(String::as_str) == Some("--book") {
+        if args.len() < 2 {
+            usage();
+        }
+        book_path = args.remove(1);
+        args.remove(0);
+    }
+
+    let book_desc = fs::read(&book_path).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", book_path, e);
+        process::exit(1);
+    });
+    //The same checks the game runs, the graph assumes every recipe makes something
+    let book = CraftingBook::from_ron(&book_desc).unwrap_or_else(|e| {
+        eprintln!("Failed to load {}: {}", book_path, e);
+        process::exit(1);
+    });
+    let graph = RecipeGraph::new(&book);
+
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["tree", item] => print!("{}", graph.dependency_tree(parse_object(item))),
+        ["cost", item] => match graph.raw_cost(parse_object(item), 1) {
+            Ok(costs) => {
+                for cost in costs {
+                    println!("{}", cost);
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+        ["check"] => {
+            let cycles = graph.cycles();
+            let unreachable = graph.unreachable();
+
+            for cycle in cycles.iter() {
+                let names: Vec<String> = cycle.iter().map(|object| object.name()).collect();
+                println!("Cycle: {}", names.join(" -> "));
+            }
+            for object in unreachable.iter() {
+                println!("Unreachable: {}", object.name());
+            }
+            for object in graph.unused() {
+                println!("Never used as an ingredient: {}", object.name());
+            }
+            let raw: Vec<String> = graph
+                .raw_resources()
+                .iter()
+                .map(|object| object.name())
+                .collect();
+            println!("Raw resources: {}", raw.join(", "));
+
+            if !cycles.is_empty() || !unreachable.is_empty() {
+                process::exit(1);
+            }
+        }
+        ["dot"] => print!("{}", graph.to_dot()),
+        ["dot", file] => fs::write(file, graph.to_dot()).unwrap_or_else(|e| {
+            eprintln!("Failed to write {}: {}", file, e);
+            process::exit(1);
+        }),
+        _ => usage(),
+    }
+}
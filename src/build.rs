@@ -1,53 +1,154 @@
 use crate::{
-    fire_graphics::{spawn_fire, ActiveFires},
+    animation::{AnimationState, Animator},
     prelude::*,
     GameState,
 };
 use bevy::prelude::*;
 
+/// How far from the player turf can be dug up or laid down, in tiles
+const TURF_REACH: f32 = 2.5;
+
 /// Only used on the player to indicate if there is a world object currently waiting
 /// to be placed
-#[derive(Component)]
+#[derive(Component, Default)]
 pub struct PlaceableGhost {
     pub to_place: Option<WorldObject>,
+    /// Turf picked from the inventory, laid down with each click until it runs out
+    pub turf: Option<TerrainType>,
 }
 
 pub struct BuildingPlugin;
 
 impl Plugin for BuildingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_update(GameState::Main).with_system(build_ghost));
+        app.add_system_set(
+            SystemSet::on_update(GameState::Main)
+                .with_system(select_turf)
+                .with_system(build_ghost.after(select_turf)),
+        );
+    }
+}
+
+/// Clicking turf in the inventory or picking it from the hotbar gets it ready to place,
+/// cancel puts it away again
+fn select_turf(
+    mut event_reader: EventReader<UIEvent>,
+    actions: Res<ActionState>,
+    mut player: Query<(&Inventory, &mut PlaceableGhost)>,
+) {
+    let (inventory, mut ghost) = player.single_mut();
+
+    let mut selected = None;
+    for ev in event_reader.iter() {
+        if let UIEventType::InventoryEvent(_, item) = ev.0 {
+            selected = Some(item.item);
+        }
+    }
+    for slot in actions.hotbar_just_pressed() {
+        selected = inventory.items.get(slot).map(|item| item.item);
+    }
+
+    match selected {
+        Some(ItemType::Turf(tile)) => ghost.turf = Some(tile),
+        Some(_) => ghost.turf = None,
+        None => {
+            if actions.just_pressed(InputAction::Cancel) && ghost.turf.is_some() {
+                ghost.turf = None;
+            }
+        }
     }
 }
 
 /// Checks for mouse clicks and an object waiting to be placed and handles the spawning of
-/// the new world object
-/// TODO needs to check against clicking through UI
+/// the new world object, without one clicks lay turf or dig with a shovel.
+/// Clicks on the UI never get here, GameInputPlugin consumes them
 fn build_ghost(
     mut commands: Commands,
     graphics: Res<Graphics>,
-    mut player: Query<&mut PlaceableGhost>,
-    mouse_input: Res<Input<MouseButton>>,
-    //TODO it would be nice to not need active fires here
-    mut active_fires: ResMut<ActiveFires>,
+    mut player: Query<(
+        &Transform,
+        &mut PlaceableGhost,
+        &mut Inventory,
+        &Equipment,
+        &mut Animator,
+    )>,
+    actions: Res<ActionState>,
     mouse_position: Res<MousePosition>,
+    terrain: Option<ResMut<Terrain>>,
+    mut messages: EventWriter<GameMessage>,
 ) {
-    let mut ghost = player.single_mut();
-
-    if mouse_input.just_pressed(MouseButton::Left) && ghost.to_place.is_some() {
-        match ghost.to_place.unwrap() {
-            WorldObject::CampFire => {
-                spawn_fire(
-                    &mut commands,
-                    &mut active_fires,
-                    &graphics,
-                    **mouse_position,
-                );
-                ghost.to_place = None;
-            }
-            _ => {
-                unreachable!("Other placement not implmented!");
+    if !actions.just_pressed(InputAction::Place) {
+        return;
+    }
+    let (transform, mut ghost, mut inventory, equipment, mut animator) = player.single_mut();
+    let position = **mouse_position;
+
+    if let Some(to_place) = ghost.to_place {
+        if let Some(terrain) = terrain.as_ref() {
+            if !terrain.can_place(to_place, position) {
+                messages.send(GameMessage::warning(format!(
+                    "Can't place the {} here",
+                    to_place.name()
+                )));
+                return;
             }
         }
+        ghost.to_place = None;
+        to_place.spawn(&mut commands, &graphics, position);
+        return;
+    }
+
+    let mut terrain = match terrain {
+        Some(terrain) => terrain,
+        None => return,
+    };
+    if ghost.turf.is_none() && equipment.tool() != Some(Tool::Shovel) {
+        return;
+    }
+    if transform.translation.truncate().distance(position) > TURF_REACH {
+        messages.send(GameMessage::info("Too far away to reach"));
+        return;
+    }
+    let tile = match terrain.tile_at(position) {
+        Some(tile) => tile,
+        None => return,
+    };
+
+    if let Some(turf) = ghost.turf {
+        if !tile.accepts_turf() {
+            messages.send(GameMessage::info(format!(
+                "{:?} has to be dug up before laying turf",
+                tile
+            )));
+            return;
+        }
+        let item = ItemAndCount {
+            item: ItemType::Turf(turf),
+            count: 1,
+        };
+        if inventory.remove(&item).is_err() {
+            ghost.turf = None;
+            return;
+        }
+        terrain.set_tile(position, turf);
+        animator.play_once(AnimationState::PickUp);
+        if inventory.count(item.item) == 0 {
+            ghost.turf = None;
+        }
+    } else if tile.is_diggable() {
+        let item = ItemAndCount {
+            item: ItemType::Turf(tile),
+            count: 1,
+        };
+        if !inventory.can_add(&item) {
+            messages.send(GameMessage::warning(format!(
+                "No room for {}",
+                item.item.name()
+            )));
+            return;
+        }
+        inventory.add(&item);
+        terrain.set_tile(position, TerrainType::Dirt);
+        animator.play_once(AnimationState::Chop);
     }
 }
@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use kayak_ui::core::{Binding, MutableBound};
+
+use crate::{game_ui::UIClock, prelude::*, GameState};
+
+pub struct CalendarPlugin;
+
+/// Length of a whole day and night in seconds
+pub const DAY_SECONDS: f32 = 240.0;
+pub const DAYS_PER_SEASON: u32 = 5;
+/// Seconds the light takes to fade in at dawn and out at dusk
+const TWILIGHT_SECONDS: f32 = 20.0;
+
+#[derive(Inspectable, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+/// Time in the world, only runs while playing
+#[derive(Inspectable, Default, Debug, Clone)]
+pub struct WorldClock {
+    /// Days since the world began, the first day is 0
+    pub day: u32,
+    /// Seconds since dawn
+    pub time_of_day: f32,
+}
+
+impl Plugin for CalendarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldClock>().add_system_set(
+            SystemSet::on_update(GameState::Main)
+                .with_system(Self::tick_clock)
+                .with_system(Self::update_clock_ui.after(Self::tick_clock)),
+        );
+    }
+}
+
+impl Season {
+    pub fn from_day(day: u32) -> Season {
+        match (day / DAYS_PER_SEASON) % 4 {
+            0 => Season::Spring,
+            1 => Season::Summer,
+            2 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+
+    /// Part of each day that is light
+    pub fn daylight_fraction(self) -> f32 {
+        match self {
+            Season::Spring => 0.55,
+            Season::Summer => 0.7,
+            Season::Autumn => 0.5,
+            Season::Winter => 0.35,
+        }
+    }
+
+    /// Change to the air temperature for the whole season
+    pub fn temperature_offset(self) -> f32 {
+        match self {
+            Season::Spring => 0.0,
+            Season::Summer => 8.0,
+            Season::Autumn => -3.0,
+            Season::Winter => -12.0,
+        }
+    }
+
+    /// How fast an object regrows this season, 0 stops it completely
+    /// TODO it would be great to describe this outside of code, in a config or something
+    pub fn growth_multiplier(self, object: WorldObject) -> f32 {
+        match (self, object) {
+            (Season::Winter, WorldObject::DeadSapling) => 0.0,
+            (Season::Winter, WorldObject::PluckedGrass) => 0.25,
+            (Season::Winter, WorldObject::GrowingTree) => 0.5,
+            (Season::Spring, WorldObject::PluckedGrass) => 2.0,
+            (Season::Spring, WorldObject::DeadSapling) => 1.5,
+            (Season::Autumn, WorldObject::PluckedGrass) => 0.75,
+            _ => 1.0,
+        }
+    }
+}
+
+impl WorldClock {
+    pub fn season(&self) -> Season {
+        Season::from_day(self.day)
+    }
+
+    /// 1 in full daylight and 0 at night, fading in between
+    pub fn daylight(&self) -> f32 {
+        let sunset = self.season().daylight_fraction() * DAY_SECONDS;
+        let dawn = (self.time_of_day / TWILIGHT_SECONDS).clamp(0.0, 1.0);
+        let dusk = ((sunset - self.time_of_day) / TWILIGHT_SECONDS).clamp(0.0, 1.0);
+        dawn.min(dusk)
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.daylight() <= 0.0
+    }
+}
+
+impl CalendarPlugin {
+    fn tick_clock(
+        time: Res<Time>,
+        mut clock: ResMut<WorldClock>,
+        mut messages: EventWriter<GameMessage>,
+    ) {
+        clock.time_of_day += time.delta_seconds();
+        if clock.time_of_day >= DAY_SECONDS {
+            clock.time_of_day -= DAY_SECONDS;
+            clock.day += 1;
+            if clock.day % DAYS_PER_SEASON == 0 {
+                messages.send(GameMessage::info(format!("{:?} has begun", clock.season())));
+            }
+        }
+    }
+
+    fn update_clock_ui(clock: Res<WorldClock>, ui_clock: Option<Res<Binding<UIClock>>>) {
+        let ui_clock = match ui_clock {
+            Some(ui_clock) => ui_clock,
+            None => return,
+        };
+        let new_clock = UIClock {
+            day: clock.day + 1,
+            season: clock.season(),
+            night: clock.is_night(),
+        };
+        //Only rebuild the UI when what it shows changes
+        if ui_clock.get() != new_clock {
+            ui_clock.set(new_clock);
+        }
+    }
+}
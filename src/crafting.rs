@@ -1,115 +1,568 @@
-use std::fs;
+use std::collections::VecDeque;
+use std::time::Duration;
 
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
 use bevy::prelude::*;
-use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, HashSet};
 use serde::Deserialize;
 
-use crate::{build::PlaceableGhost, prelude::*};
+use crate::{
+    assets::DataAssets, build::PlaceableGhost, inventory::INVENTORY_ITEM_SIZE, prelude::*,
+    GameState,
+};
 
-/// Currently Unused but needs to be reincorperated with UI to render the outline
-/// of the box and grey out contents (or deleted)
-#[derive(Component, Inspectable)]
-pub struct CraftingBox {
-    //TODO grey out currently impossible recipes
-    active: bool,
-    //This is always a valid index, enforce this
-    recipe_index: usize,
-}
-
-/// Resource loaded from recipes.ron
-/// Contains all valid recipes currently in the game
-#[derive(Clone, Deserialize)]
+/// Asset and resource loaded from crafting.recipes.ron
+/// Contains all valid recipes currently in the game, the resource is only replaced
+/// once a newly loaded asset passes validation
+#[derive(Clone, Default, Deserialize, TypeUuid)]
+#[uuid = "3c1e7a52-5d0b-4a8e-9f41-6d2b8c0e7f13"]
 pub struct CraftingBook {
-    pub(crate) recipes: Vec<CraftingRecipe>,
+    pub recipes: Vec<CraftingRecipe>,
 }
 
 // The description of a single recipe
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct CraftingRecipe {
-    pub(crate) needed: Vec<ItemAndCount>,
-    pub(crate) produces: WorldObject,
+    /// Used up by crafting
+    pub needed: Vec<ItemAndCount>,
+    /// Needed to craft but never used up, tools count when held in the hand too
+    #[serde(default)]
+    pub catalysts: Vec<ItemAndCount>,
+    /// The main product, also used to identify the recipe in the UI
+    pub produces: WorldObject,
+    /// How many of the main product a single craft makes
+    #[serde(default = "default_count")]
+    pub count: usize,
+    /// Extra items made alongside the main product
+    #[serde(default)]
+    pub byproducts: Vec<ItemAndCount>,
+    /// Seconds it takes to craft a single item
+    #[serde(default = "default_craft_time")]
+    pub craft_time: f32,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+fn default_craft_time() -> f32 {
+    0.5
+}
+
+impl CraftingRecipe {
+    /// Everything a single craft makes, main product first
+    pub fn outputs(&self) -> Vec<(WorldObject, usize)> {
+        std::iter::once((self.produces, self.count))
+            .chain(
+                self.byproducts
+                    .iter()
+                    .map(|byproduct| (WorldObject::Item(byproduct.item), byproduct.count)),
+            )
+            .collect()
+    }
+
+    /// Every item the recipe mentions besides its main product, labelled with its role
+    pub fn items(&self) -> impl Iterator<Item = (&'static str, &ItemAndCount)> {
+        self.needed
+            .iter()
+            .map(|item| ("ingredient", item))
+            .chain(self.catalysts.iter().map(|item| ("catalyst", item)))
+            .chain(self.byproducts.iter().map(|item| ("byproduct", item)))
+    }
+
+    /// Catalysts can be in the inventory or held, held only counts as one
+    pub fn catalysts_available(&self, inventory: &Inventory, held: Option<ItemType>) -> bool {
+        self.catalysts.iter().all(|catalyst| {
+            let held_count = if held == Some(catalyst.item) { 1 } else { 0 };
+            inventory.count(catalyst.item) + held_count >= catalyst.count
+        })
+    }
+}
+
+/// Lives on the player, everything clicked in the crafting menu waits here until
+/// it has been crafted
+#[derive(Component, Default)]
+pub struct CraftingQueue {
+    pub(crate) queue: VecDeque<WorldObject>,
+    pub(crate) in_progress: Option<CraftInProgress>,
+}
+
+/// The item currently being crafted, ingredients have already been taken from the inventory
+pub struct CraftInProgress {
+    //Kept so a hot reload can't change what gets refunded
+    pub(crate) recipe: CraftingRecipe,
+    pub(crate) timer: Timer,
+}
+
+impl CraftingQueue {
+    /// How far along the current craft is, used to draw progress over the recipe icon
+    pub fn progress(&self) -> Option<(WorldObject, f32)> {
+        self.in_progress
+            .as_ref()
+            .map(|craft| (craft.recipe.produces, shown_percent(&craft.timer)))
+    }
+}
+
+/// Craft progress rounded down to whole percents, finer steps aren't visible on the bar
+fn shown_percent(timer: &Timer) -> f32 {
+    (timer.percent() * 100.0).floor() / 100.0
+}
+
+/// Parses and validates *.recipes.ron files, sprites are only checked once graphics exist
+#[derive(Default)]
+pub struct CraftingBookLoader;
+
+impl AssetLoader for CraftingBookLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let crafting_book = CraftingBook::from_ron(bytes).map_err(|e| {
+                GameError::new(
+                    e.error_type,
+                    format!(
+                        "Failed to load {:?}: {}",
+                        load_context.path(),
+                        e.error_payload
+                    ),
+                )
+            })?;
+            load_context.set_default_asset(LoadedAsset::new(crafting_book));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["recipes.ron"]
+    }
 }
 
 pub struct CraftingPlugin;
 
 impl Plugin for CraftingPlugin {
     fn build(&self, app: &mut App) {
-        let crafting_desc = fs::read_to_string("assets/recipes.ron").unwrap();
+        app.add_asset::<CraftingBook>()
+            .init_asset_loader::<CraftingBookLoader>()
+            .init_resource::<CraftingBook>()
+            .add_system(Self::update_crafting_book)
+            .add_system_set(
+                SystemSet::on_update(GameState::Main)
+                    .with_system(Self::craft_item)
+                    .with_system(Self::progress_crafting.after(Self::craft_item))
+                    .with_system(Self::cancel_crafting.before(Self::progress_crafting)),
+            );
+    }
+}
+
+impl CraftingBook {
+    /// Parses and validates a recipe file, shared by the asset loader and the recipe_graph tool
+    /// so a broken book is rejected before anything reads it
+    pub fn from_ron(bytes: &[u8]) -> Result<Self, GameError> {
+        let book: CraftingBook = ron::de::from_bytes(bytes).map_err(|e| {
+            GameError::new(
+                GameErrorType::AssetLoadFailed,
+                format!("Parse error: {}", e),
+            )
+        })?;
+        let errors = book.validate();
+        if errors.is_empty() {
+            Ok(book)
+        } else {
+            Err(GameError::new(
+                GameErrorType::InvalidRecipe,
+                errors
+                    .iter()
+                    .map(|error| error.error_payload.clone())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ))
+        }
+    }
+
+    /// Checks the book for mistakes that the parser can't catch, every problem found is returned.
+    /// Needs no graphics so it runs while loading, sprites are checked by missing_graphics
+    pub fn validate(&self) -> Vec<GameError> {
+        let mut errors = Vec::new();
+        let mut products = HashSet::default();
+
+        for recipe in self.recipes.iter() {
+            if !products.insert(recipe.produces) {
+                errors.push(GameError::new(
+                    GameErrorType::InvalidRecipe,
+                    format!("Duplicate recipe for {:?}", recipe.produces),
+                ));
+            }
+            if recipe.produces == WorldObject::Item(ItemType::None) {
+                errors.push(GameError::new(
+                    GameErrorType::InvalidRecipe,
+                    "Recipe produces unknown item None".to_string(),
+                ));
+            }
+            if recipe.count == 0 {
+                errors.push(GameError::new(
+                    GameErrorType::InvalidRecipe,
+                    format!("Recipe for {:?} produces zero items", recipe.produces),
+                ));
+            }
+            if recipe.count > INVENTORY_ITEM_SIZE {
+                errors.push(GameError::new(
+                    GameErrorType::InvalidRecipe,
+                    format!(
+                        "Recipe for {:?} produces more than a stack of {}",
+                        recipe.produces, INVENTORY_ITEM_SIZE
+                    ),
+                ));
+            }
+            //There is only a single ghost to hold placeables
+            if !matches!(recipe.produces, WorldObject::Item(_)) && recipe.count != 1 {
+                errors.push(GameError::new(
+                    GameErrorType::InvalidRecipe,
+                    format!(
+                        "Placeable {:?} can only be crafted one at a time",
+                        recipe.produces
+                    ),
+                ));
+            }
+            if recipe.craft_time < 0.0 {
+                errors.push(GameError::new(
+                    GameErrorType::InvalidRecipe,
+                    format!("Recipe for {:?} has a negative craft time", recipe.produces),
+                ));
+            }
+            if recipe.needed.is_empty() {
+                errors.push(GameError::new(
+                    GameErrorType::InvalidRecipe,
+                    format!("Recipe for {:?} has no ingredients", recipe.produces),
+                ));
+            }
 
-        let crafting_book: CraftingBook = ron::de::from_str(&crafting_desc).unwrap_or_else(|e| {
-            println!("Failed to load config: {}", e);
-            std::process::exit(1);
-        });
+            for (kind, item) in recipe.items() {
+                if item.item == ItemType::None {
+                    errors.push(GameError::new(
+                        GameErrorType::InvalidRecipe,
+                        format!("Recipe for {:?} has unknown {} None", recipe.produces, kind),
+                    ));
+                }
+                if item.count == 0 {
+                    errors.push(GameError::new(
+                        GameErrorType::InvalidRecipe,
+                        format!(
+                            "Recipe for {:?} has zero {:?} as {}",
+                            recipe.produces, item.item, kind
+                        ),
+                    ));
+                }
+                if item.count > INVENTORY_ITEM_SIZE {
+                    errors.push(GameError::new(
+                        GameErrorType::InvalidRecipe,
+                        format!(
+                            "Recipe for {:?} has more than a stack of {:?} as {}",
+                            recipe.produces, item.item, kind
+                        ),
+                    ));
+                }
+            }
+        }
+        errors
+    }
 
-        app.insert_resource(crafting_book)
-            .add_system(Self::craft_item)
-            .register_inspectable::<CraftingBox>();
+    /// Every object in the book needs a sprite, products and ingredients are looked up in
+    /// the same table so a recipe can't pass with an item the world can't draw
+    pub fn missing_graphics(&self, graphics: &Graphics) -> Vec<GameError> {
+        let mut errors = Vec::new();
+        for recipe in self.recipes.iter() {
+            if !graphics.item_map.contains_key(&recipe.produces) {
+                errors.push(GameError::new(
+                    GameErrorType::InvalidRecipe,
+                    format!("No graphic for product {:?}", recipe.produces),
+                ));
+            }
+            for (kind, item) in recipe.items() {
+                if !graphics
+                    .item_map
+                    .contains_key(&WorldObject::Item(item.item))
+                {
+                    errors.push(GameError::new(
+                        GameErrorType::InvalidRecipe,
+                        format!(
+                            "No graphic for {} {:?} in recipe for {:?}",
+                            kind, item.item, recipe.produces
+                        ),
+                    ));
+                }
+            }
+        }
+        errors
     }
 }
 
 impl CraftingPlugin {
+    /// Watches for the recipe asset being loaded or hot reloaded and swaps in the new book
+    /// if it is valid, otherwise the errors are reported and the old book is kept
+    fn update_crafting_book(
+        mut asset_events: EventReader<AssetEvent<CraftingBook>>,
+        mut pending: Local<bool>,
+        data_assets: Option<Res<DataAssets>>,
+        book_assets: Res<Assets<CraftingBook>>,
+        graphics: Option<Res<Graphics>>,
+        mut crafting_book: ResMut<CraftingBook>,
+    ) {
+        //The collection only exists once loading is done, the book is already loaded by then
+        let book_handle = match data_assets {
+            Some(data_assets) => {
+                if data_assets.is_added() {
+                    *pending = true;
+                }
+                data_assets.crafting_book.clone()
+            }
+            None => return,
+        };
+        for ev in asset_events.iter() {
+            match ev {
+                AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                    if *handle == book_handle {
+                        *pending = true;
+                    }
+                }
+                AssetEvent::Removed { .. } => {}
+            }
+        }
+
+        //Sprites can only be checked once the graphics are loaded
+        let graphics = match graphics {
+            Some(graphics) if *pending => graphics,
+            _ => return,
+        };
+        *pending = false;
+
+        let new_book = match book_assets.get(&book_handle) {
+            Some(book) => book,
+            None => return,
+        };
+
+        let errors = new_book.missing_graphics(&graphics);
+        if errors.is_empty() {
+            info!("Loaded {} crafting recipes", new_book.recipes.len());
+            *crafting_book = new_book.clone();
+        } else {
+            for error in errors {
+                error!("{}", error);
+            }
+        }
+    }
+
     /// A system to respond to crafting UI Events
-    /// Checks if the player can craft something, manages inventory juggling, and sets the ghost if
-    /// the item is placeable
+    /// Queues up the clicked recipe, holding CraftMany queues as many as the inventory allows
     fn craft_item(
         mut event_reader: EventReader<UIEvent>,
-        mut inventory_query: Query<(&mut Inventory, &mut PlaceableGhost), With<Player>>,
+        actions: Res<ActionState>,
+        mut crafting_query: Query<(&Inventory, &Equipment, &mut CraftingQueue), With<Player>>,
         crafting_book: Res<CraftingBook>,
+        mut messages: EventWriter<GameMessage>,
     ) {
         for ev in event_reader.iter() {
             if let UIEventType::CraftEvent(item) = ev.0 {
                 // get player inventory
-                let (mut inventory, mut ghost) = inventory_query.single_mut();
+                let (inventory, equipment, mut crafting_queue) = crafting_query.single_mut();
+                let held = equipment.hand;
 
                 // find recipe to craft
-                let recipe_to_craft = crafting_book
+                let recipe_to_craft = match crafting_book
                     .recipes
                     .iter()
-                    .filter(|recipe| recipe.produces == item)
-                    .collect::<Vec<&CraftingRecipe>>()[0];
-
-                match recipe_to_craft.produces {
-                    WorldObject::Item(item) => {
-                        // make sure inventory has ingredients and space to store new item
-                        if inventory.ingredients_available(recipe_to_craft)
-                            && inventory.can_add(&ItemAndCount {
-                                item: item,
-                                count: 1,
-                            })
-                        {
-                            // remove ingredients
-                            recipe_to_craft.needed.iter().for_each(|ingredient| {
-                                inventory
-                                    .remove(ingredient)
-                                    .expect("removing ingredients failed")
-                            });
-                            // add newly crafted item
-                            inventory.add(&ItemAndCount {
-                                item: item,
-                                count: 1,
-                            });
-                        } else {
-                            info!("either not enough ingredients or not enough space in inventory");
-                        }
-                    }
-                    //Making something placeable
-                    _ => {
-                        if inventory.ingredients_available(recipe_to_craft) {
-                            // remove ingredients
-                            recipe_to_craft.needed.iter().for_each(|ingredient| {
-                                inventory
-                                    .remove(ingredient)
-                                    .expect("removing ingredients failed")
-                            });
-                            ghost.to_place = Some(recipe_to_craft.produces);
-                        } else {
-                            info!("Not enough items!");
-                        }
+                    .find(|recipe| recipe.produces == item)
+                {
+                    Some(recipe) => recipe,
+                    None => {
+                        messages.send(
+                            GameError::new(
+                                GameErrorType::InvalidRecipe,
+                                format!("No recipe produces {}", item.name()),
+                            )
+                            .into(),
+                        );
+                        continue;
                     }
+                };
+
+                //A single craft is never blocked by space, extra output is dropped on the ground
+                let count = if actions.pressed(InputAction::CraftMany) {
+                    inventory.max_craftable(recipe_to_craft, held)
+                } else if inventory.can_craft(recipe_to_craft, held) {
+                    1
+                } else {
+                    0
+                };
+
+                if count == 0 {
+                    messages.send(
+                        GameError::new(
+                            GameErrorType::CraftingFailed,
+                            if inventory.can_craft(recipe_to_craft, held) {
+                                format!("No room to craft any more {}", item.name())
+                            } else {
+                                format!("Not enough items to craft {}", item.name())
+                            },
+                        )
+                        .into(),
+                    );
+                    continue;
+                }
+
+                for _ in 0..count {
+                    crafting_queue.queue.push_back(recipe_to_craft.produces);
                 }
             }
         }
     }
+
+    /// Starts the next queued craft by taking its ingredients, ticks the current one and
+    /// hands out the result when it is done. Placeables wait until the ghost is free and
+    /// items that don't fit in the inventory are dropped at the players feet
+    fn progress_crafting(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut crafting_query: Query<
+            (
+                &Transform,
+                &Equipment,
+                &mut CraftingQueue,
+                &mut Inventory,
+                &mut PlaceableGhost,
+            ),
+            With<Player>,
+        >,
+        crafting_book: Res<CraftingBook>,
+        graphics: Res<Graphics>,
+        mut messages: EventWriter<GameMessage>,
+        mut unshown: Local<Duration>,
+    ) {
+        let (transform, equipment, mut crafting_queue, mut inventory, mut ghost) =
+            crafting_query.single_mut();
+
+        if crafting_queue.in_progress.is_none() {
+            *unshown = Duration::ZERO;
+            //Only touch the queue mutably when there is work so change detection stays quiet
+            let next = match crafting_queue.queue.front() {
+                Some(next) => *next,
+                None => return,
+            };
+            crafting_queue.queue.pop_front();
+            let recipe = match crafting_book
+                .recipes
+                .iter()
+                .find(|recipe| recipe.produces == next)
+            {
+                Some(recipe) => recipe,
+                None => {
+                    messages.send(
+                        GameError::new(
+                            GameErrorType::InvalidRecipe,
+                            format!("Recipe for queued {} no longer exists", next.name()),
+                        )
+                        .into(),
+                    );
+                    return;
+                }
+            };
+            if !inventory.can_craft(recipe, equipment.hand) {
+                messages.send(
+                    GameError::new(
+                        GameErrorType::CraftingFailed,
+                        format!("Not enough items to craft queued {}", next.name()),
+                    )
+                    .into(),
+                );
+                return;
+            }
+            // remove ingredients
+            recipe.needed.iter().for_each(|ingredient| {
+                inventory
+                    .remove(ingredient)
+                    .expect("removing ingredients failed")
+            });
+            crafting_queue.in_progress = Some(CraftInProgress {
+                recipe: recipe.clone(),
+                timer: Timer::from_seconds(recipe.craft_time, false),
+            });
+        }
+
+        //Time is only written to the queue when the shown progress moves, every write
+        //rebuilds the inventory UI
+        let craft = crafting_queue
+            .in_progress
+            .as_ref()
+            .expect("craft was just started");
+        let mut timer = craft.timer.clone();
+        timer.tick(*unshown + time.delta());
+        let produces = craft.recipe.produces;
+        let finished = timer.finished();
+        if shown_percent(&timer) != shown_percent(&craft.timer) {
+            *unshown = Duration::ZERO;
+            crafting_queue.in_progress.as_mut().unwrap().timer = timer;
+        } else if !finished {
+            *unshown += time.delta();
+        }
+        if !finished {
+            return;
+        }
+
+        //Placeables are always the main product, hold everything until the ghost is free
+        if !matches!(produces, WorldObject::Item(_)) && ghost.to_place.is_some() {
+            return;
+        }
+        *unshown = Duration::ZERO;
+        let craft = crafting_queue
+            .in_progress
+            .take()
+            .expect("craft was just checked");
+
+        let position = transform.translation.truncate();
+        for overflow in inventory.add_outputs(&craft.recipe) {
+            messages.send(GameMessage::info(format!(
+                "No room for the crafted {}, dropped it",
+                overflow.item.name()
+            )));
+            overflow.spawn_on_ground(&mut commands, &graphics, position);
+        }
+        //Making something placeable
+        if !matches!(craft.recipe.produces, WorldObject::Item(_)) {
+            ghost.to_place = Some(craft.recipe.produces);
+        }
+    }
+
+    /// Cancel clears the crafting queue and refunds whatever was being crafted,
+    /// catalysts were never taken so only the used up ingredients come back
+    fn cancel_crafting(
+        mut commands: Commands,
+        actions: Res<ActionState>,
+        mut crafting_query: Query<(&Transform, &mut CraftingQueue, &mut Inventory), With<Player>>,
+        graphics: Res<Graphics>,
+        mut messages: EventWriter<GameMessage>,
+    ) {
+        if !actions.just_pressed(InputAction::Cancel) {
+            return;
+        }
+        let (transform, mut crafting_queue, mut inventory) = crafting_query.single_mut();
+        crafting_queue.queue.clear();
+
+        if let Some(craft) = crafting_queue.in_progress.take() {
+            for overflow in inventory.refund(&craft.recipe) {
+                messages.send(GameMessage::info(format!(
+                    "No room to refund the {}, dropped it",
+                    overflow.item.name()
+                )));
+                overflow.spawn_on_ground(
+                    &mut commands,
+                    &graphics,
+                    transform.translation.truncate(),
+                );
+            }
+        }
+    }
 }
 
 impl Inventory {
@@ -121,4 +574,275 @@ impl Inventory {
         }
         true
     }
+
+    /// Ingredients and catalysts are both available, held is whatever is in the players hand
+    pub fn can_craft(&self, recipe: &CraftingRecipe, held: Option<ItemType>) -> bool {
+        self.ingredients_available(recipe) && recipe.catalysts_available(self, held)
+    }
+
+    /// Adds every item a finished craft makes, whatever doesn't fit is returned to be dropped.
+    /// Placeable products are left to the caller
+    pub fn add_outputs(&mut self, recipe: &CraftingRecipe) -> Vec<ItemAndCount> {
+        let items = recipe
+            .outputs()
+            .into_iter()
+            .filter_map(|(output, count)| match output {
+                WorldObject::Item(item) => Some(ItemAndCount {
+                    item: item,
+                    count: count,
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        self.add_all(&items)
+    }
+
+    /// Gives back the used up ingredients of a cancelled craft, whatever doesn't fit is returned
+    pub fn refund(&mut self, recipe: &CraftingRecipe) -> Vec<ItemAndCount> {
+        self.add_all(&recipe.needed)
+    }
+
+    fn add_all(&mut self, items: &[ItemAndCount]) -> Vec<ItemAndCount> {
+        items
+            .iter()
+            .filter_map(|item| {
+                self.add(item).map(|overflow| ItemAndCount {
+                    item: item.item,
+                    count: overflow.0,
+                })
+            })
+            .collect()
+    }
+
+    /// How many times the recipe can be crafted back to back with the current inventory
+    /// without any of the output overflowing
+    pub fn max_craftable(&self, recipe: &CraftingRecipe, held: Option<ItemType>) -> usize {
+        let mut inventory = self.clone();
+        let mut count = 0;
+        while inventory.can_craft(recipe, held) {
+            for ingredient in recipe.needed.iter() {
+                inventory
+                    .remove(ingredient)
+                    .expect("removing ingredients failed");
+            }
+            if !inventory.add_outputs(recipe).is_empty() {
+                break;
+            }
+            count += 1;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A book with a single recipe making flint from twigs, recipe is spliced in after it
+    fn book_with(recipe: &str) -> String {
+        format!(
+            "(recipes: [
+                CraftingRecipe(
+                    needed: [ItemAndCount(item: Twig, count: 2)],
+                    produces: Item(Flint),
+                ),
+                {}
+            ])",
+            recipe
+        )
+    }
+
+    #[test]
+    fn shipped_book_is_valid() {
+        let book = CraftingBook::from_ron(include_bytes!("../assets/crafting.recipes.ron"));
+        assert!(book.is_ok(), "{}", book.err().unwrap());
+    }
+
+    #[test]
+    fn good_book_loads() {
+        let desc = book_with(
+            "CraftingRecipe(
+                needed: [ItemAndCount(item: Flint, count: 1)],
+                catalysts: [ItemAndCount(item: Tool(Axe), count: 1)],
+                produces: Item(Wood),
+                count: 2,
+            )",
+        );
+        let book = CraftingBook::from_ron(desc.as_bytes()).ok().unwrap();
+        assert_eq!(book.recipes.len(), 2);
+        assert_eq!(book.recipes[0].count, 1);
+        assert_eq!(book.recipes[1].count, 2);
+    }
+
+    #[test]
+    fn unknown_item_is_an_error() {
+        let desc = book_with(
+            "CraftingRecipe(
+                needed: [ItemAndCount(item: Diamond, count: 1)],
+                produces: Item(Wood),
+            )",
+        );
+        assert!(CraftingBook::from_ron(desc.as_bytes()).is_err());
+
+        let desc = book_with(
+            "CraftingRecipe(
+                needed: [ItemAndCount(item: None, count: 1)],
+                produces: Item(Wood),
+            )",
+        );
+        assert!(CraftingBook::from_ron(desc.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn duplicate_recipe_is_an_error() {
+        let desc = book_with(
+            "CraftingRecipe(
+                needed: [ItemAndCount(item: Grass, count: 3)],
+                produces: Item(Flint),
+            )",
+        );
+        assert!(CraftingBook::from_ron(desc.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn zero_counts_are_errors() {
+        let desc = book_with(
+            "CraftingRecipe(
+                needed: [ItemAndCount(item: Flint, count: 1)],
+                produces: Item(Wood),
+                count: 0,
+            )",
+        );
+        assert!(CraftingBook::from_ron(desc.as_bytes()).is_err());
+
+        let desc = book_with(
+            "CraftingRecipe(
+                needed: [ItemAndCount(item: Flint, count: 0)],
+                produces: Item(Wood),
+            )",
+        );
+        assert!(CraftingBook::from_ron(desc.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn more_than_a_stack_is_an_error() {
+        let desc = book_with(
+            "CraftingRecipe(
+                needed: [ItemAndCount(item: Flint, count: 1)],
+                produces: Item(Wood),
+                count: 6,
+            )",
+        );
+        assert!(CraftingBook::from_ron(desc.as_bytes()).is_err());
+
+        let desc = book_with(
+            "CraftingRecipe(
+                needed: [ItemAndCount(item: Flint, count: 6)],
+                produces: Item(Wood),
+            )",
+        );
+        assert!(CraftingBook::from_ron(desc.as_bytes()).is_err());
+    }
+
+    /// 3 grass makes 4 twigs with a flint on the side
+    fn twig_recipe() -> CraftingRecipe {
+        CraftingRecipe {
+            needed: vec![ItemAndCount {
+                item: ItemType::Grass,
+                count: 3,
+            }],
+            catalysts: Vec::new(),
+            produces: WorldObject::Item(ItemType::Twig),
+            count: 4,
+            byproducts: vec![ItemAndCount {
+                item: ItemType::Flint,
+                count: 1,
+            }],
+            craft_time: 1.0,
+        }
+    }
+
+    fn stack(item: ItemType, count: usize) -> ItemAndCount {
+        ItemAndCount { item, count }
+    }
+
+    #[test]
+    fn multi_count_craft_fills_stacks() {
+        let recipe = twig_recipe();
+        let mut inventory = Inventory::with_capacity(4);
+        inventory.items[0] = stack(ItemType::Grass, 3);
+        inventory.items[1] = stack(ItemType::Twig, 2);
+
+        for ingredient in recipe.needed.iter() {
+            inventory.remove(ingredient).unwrap();
+        }
+        assert!(inventory.add_outputs(&recipe).is_empty());
+        //The existing stack is topped up to the limit before a new one is started
+        assert_eq!(
+            inventory.items,
+            vec![
+                stack(ItemType::Twig, 1),
+                stack(ItemType::Twig, 5),
+                stack(ItemType::Flint, 1),
+                ItemAndCount::default(),
+            ]
+        );
+    }
+
+    #[test]
+    fn craft_output_that_does_not_fit_is_returned() {
+        let recipe = twig_recipe();
+        let mut inventory = Inventory::with_capacity(1);
+        inventory.items[0] = stack(ItemType::Twig, 4);
+
+        assert_eq!(
+            inventory.add_outputs(&recipe),
+            vec![stack(ItemType::Twig, 3), stack(ItemType::Flint, 1)]
+        );
+        assert_eq!(inventory.items, vec![stack(ItemType::Twig, 5)]);
+        assert_eq!(inventory.max_craftable(&recipe, None), 0);
+    }
+
+    #[test]
+    fn refund_gives_back_every_ingredient() {
+        let mut recipe = twig_recipe();
+        recipe.needed.push(stack(ItemType::Wood, 4));
+        let mut inventory = Inventory::with_capacity(3);
+        inventory.items[0] = stack(ItemType::Wood, 3);
+        inventory.items[1] = stack(ItemType::Twig, 5);
+
+        assert_eq!(inventory.refund(&recipe), vec![stack(ItemType::Wood, 2)]);
+        assert_eq!(
+            inventory.items,
+            vec![
+                stack(ItemType::Wood, 5),
+                stack(ItemType::Twig, 5),
+                stack(ItemType::Grass, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let desc = book_with(
+            "CraftingRecipe(
+                needed: [ItemAndCount(item: Twig, count: 0)],
+                produces: Item(Flint),
+                count: 0,
+            )",
+        );
+        let book: CraftingBook = ron::de::from_str(&desc).unwrap();
+        assert_eq!(book.validate().len(), 3);
+    }
+
+    #[test]
+    fn progress_is_shown_in_whole_percents() {
+        let mut timer = Timer::from_seconds(10.0, false);
+        timer.tick(Duration::from_millis(1234));
+        assert_eq!(shown_percent(&timer), 0.12);
+        timer.tick(Duration::from_millis(5));
+        assert_eq!(shown_percent(&timer), 0.12);
+        timer.tick(Duration::from_secs(10));
+        assert_eq!(shown_percent(&timer), 1.0);
+    }
 }
@@ -0,0 +1,511 @@
+use std::fmt::Write;
+
+use bevy::utils::{HashMap, HashSet};
+
+use crate::{
+    crafting::{CraftingBook, CraftingRecipe},
+    prelude::*,
+};
+
+/// Whole crafts needed to make count of a recipes product, None if the recipe makes nothing
+fn crafts_needed(recipe: &CraftingRecipe, count: usize) -> Option<usize> {
+    match recipe.count {
+        0 => None,
+        per_craft => Some((count + per_craft - 1) / per_craft),
+    }
+}
+
+/// Static analysis of a CraftingBook, treats every recipe as an edge from each of its
+/// ingredients to its product. Used by the recipe_graph tool so it needs no bevy app
+pub struct RecipeGraph<'a> {
+    book: &'a CraftingBook,
+    recipes: HashMap<WorldObject, &'a CraftingRecipe>,
+}
+
+impl<'a> RecipeGraph<'a> {
+    pub fn new(book: &'a CraftingBook) -> Self {
+        let recipes = book
+            .recipes
+            .iter()
+            .map(|recipe| (recipe.produces, recipe))
+            .collect();
+        Self { book, recipes }
+    }
+
+    pub fn recipe_for(&self, object: WorldObject) -> Option<&'a CraftingRecipe> {
+        self.recipes.get(&object).copied()
+    }
+
+    /// Every object mentioned in the book, in the order they first appear
+    pub fn objects(&self) -> Vec<WorldObject> {
+        let mut objects = Vec::new();
+        for recipe in self.book.recipes.iter() {
+            for ingredient in recipe.needed.iter().chain(recipe.catalysts.iter()) {
+                let ingredient = WorldObject::Item(ingredient.item);
+                if !objects.contains(&ingredient) {
+                    objects.push(ingredient);
+                }
+            }
+            for (output, _) in recipe.outputs() {
+                if !objects.contains(&output) {
+                    objects.push(output);
+                }
+            }
+        }
+        objects
+    }
+
+    /// Things that no recipe has as its main product, these have to be gathered from the
+    /// world (or show up as a byproduct)
+    pub fn raw_resources(&self) -> Vec<WorldObject> {
+        self.objects()
+            .into_iter()
+            .filter(|object| self.recipe_for(*object).is_none())
+            .collect()
+    }
+
+    /// Products that are never used as an ingredient or catalyst, fine for tools and
+    /// placeables but usually a mistake for intermediate items
+    pub fn unused(&self) -> Vec<WorldObject> {
+        let used: HashSet<WorldObject> = self
+            .book
+            .recipes
+            .iter()
+            .flat_map(|recipe| recipe.needed.iter().chain(recipe.catalysts.iter()))
+            .map(|ingredient| WorldObject::Item(ingredient.item))
+            .collect();
+        self.book
+            .recipes
+            .iter()
+            .map(|recipe| recipe.produces)
+            .filter(|product| !used.contains(product))
+            .collect()
+    }
+
+    /// Products which can never be crafted starting from raw resources,
+    /// this only happens when recipes depend on each other in a loop
+    pub fn unreachable(&self) -> Vec<WorldObject> {
+        let mut reachable: HashSet<WorldObject> = self.raw_resources().into_iter().collect();
+        loop {
+            let newly_reachable: Vec<WorldObject> = self
+                .book
+                .recipes
+                .iter()
+                .filter(|recipe| !reachable.contains(&recipe.produces))
+                .filter(|recipe| {
+                    recipe
+                        .needed
+                        .iter()
+                        .chain(recipe.catalysts.iter())
+                        .all(|ingredient| reachable.contains(&WorldObject::Item(ingredient.item)))
+                })
+                .map(|recipe| recipe.produces)
+                .collect();
+            if newly_reachable.is_empty() {
+                break;
+            }
+            reachable.extend(newly_reachable);
+        }
+        self.book
+            .recipes
+            .iter()
+            .map(|recipe| recipe.produces)
+            .filter(|product| !reachable.contains(product))
+            .collect()
+    }
+
+    /// Every loop of recipes, each cycle is listed starting and ending on the same object
+    pub fn cycles(&self) -> Vec<Vec<WorldObject>> {
+        let mut cycles = Vec::new();
+        let mut finished = HashSet::default();
+        for object in self.objects() {
+            let mut path = Vec::new();
+            self.find_cycles(object, &mut path, &mut finished, &mut cycles);
+        }
+        cycles
+    }
+
+    fn find_cycles(
+        &self,
+        object: WorldObject,
+        path: &mut Vec<WorldObject>,
+        finished: &mut HashSet<WorldObject>,
+        cycles: &mut Vec<Vec<WorldObject>>,
+    ) {
+        if let Some(start) = path.iter().position(|visited| *visited == object) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(object);
+            cycles.push(cycle);
+            return;
+        }
+        if finished.contains(&object) {
+            return;
+        }
+        if let Some(recipe) = self.recipe_for(object) {
+            path.push(object);
+            for ingredient in recipe.needed.iter().chain(recipe.catalysts.iter()) {
+                self.find_cycles(WorldObject::Item(ingredient.item), path, finished, cycles);
+            }
+            path.pop();
+        }
+        finished.insert(object);
+    }
+
+    /// The total amount of raw resources needed to craft count of an object from scratch,
+    /// recipes making several items are rounded up to whole crafts and catalysts are free
+    pub fn raw_cost(
+        &self,
+        object: WorldObject,
+        count: usize,
+    ) -> Result<Vec<ItemAndCount>, GameError> {
+        let mut totals = Vec::new();
+        self.add_raw_cost(object, count, &mut Vec::new(), &mut totals)?;
+        Ok(totals)
+    }
+
+    fn add_raw_cost(
+        &self,
+        object: WorldObject,
+        count: usize,
+        path: &mut Vec<WorldObject>,
+        totals: &mut Vec<ItemAndCount>,
+    ) -> Result<(), GameError> {
+        if path.contains(&object) {
+            return Err(GameError::new(
+                GameErrorType::InvalidRecipe,
+                format!("Recipe cycle through {:?}", object),
+            ));
+        }
+        match (self.recipe_for(object), object) {
+            (Some(recipe), _) => {
+                let crafts = crafts_needed(recipe, count).ok_or_else(|| {
+                    GameError::new(
+                        GameErrorType::InvalidRecipe,
+                        format!("Recipe for {:?} produces zero items", object),
+                    )
+                })?;
+                path.push(object);
+                for ingredient in recipe.needed.iter() {
+                    self.add_raw_cost(
+                        WorldObject::Item(ingredient.item),
+                        ingredient.count * crafts,
+                        path,
+                        totals,
+                    )?;
+                }
+                path.pop();
+            }
+            (None, WorldObject::Item(item)) => {
+                match totals.iter_mut().find(|total| total.item == item) {
+                    Some(total) => total.count += count,
+                    None => totals.push(ItemAndCount { item, count }),
+                }
+            }
+            (None, _) => {
+                return Err(GameError::new(
+                    GameErrorType::InvalidRecipe,
+                    format!("Nothing produces {:?}", object),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Human readable tree of everything needed to craft an object
+    pub fn dependency_tree(&self, object: WorldObject) -> String {
+        let mut tree = String::new();
+        self.write_tree(object, 1, 0, &mut Vec::new(), &mut tree);
+        tree
+    }
+
+    fn write_tree(
+        &self,
+        object: WorldObject,
+        count: usize,
+        depth: usize,
+        path: &mut Vec<WorldObject>,
+        tree: &mut String,
+    ) {
+        let indent = "  ".repeat(depth);
+        if path.contains(&object) {
+            let _ = writeln!(tree, "{}{}x {} (cycle!)", indent, count, object.name());
+            return;
+        }
+        match self.recipe_for(object) {
+            Some(recipe) if recipe.count == 0 => {
+                let _ = writeln!(
+                    tree,
+                    "{}{}x {} (makes nothing!)",
+                    indent,
+                    count,
+                    object.name()
+                );
+            }
+            Some(recipe) => {
+                let crafts = crafts_needed(recipe, count).unwrap_or(0);
+                let _ = writeln!(
+                    tree,
+                    "{}{}x {} ({} crafts, {:.1}s)",
+                    indent,
+                    count,
+                    object.name(),
+                    crafts,
+                    recipe.craft_time * crafts as f32
+                );
+                path.push(object);
+                for ingredient in recipe.needed.iter() {
+                    self.write_tree(
+                        WorldObject::Item(ingredient.item),
+                        ingredient.count * crafts,
+                        depth + 1,
+                        path,
+                        tree,
+                    );
+                }
+                for catalyst in recipe.catalysts.iter() {
+                    let _ = writeln!(tree, "{}  {} (catalyst, kept)", indent, catalyst);
+                }
+                for byproduct in recipe.byproducts.iter() {
+                    let _ = writeln!(
+                        tree,
+                        "{}  +{}x {:?} (byproduct)",
+                        indent,
+                        byproduct.count * crafts,
+                        byproduct.item
+                    );
+                }
+                path.pop();
+            }
+            None => {
+                let _ = writeln!(tree, "{}{}x {} (raw)", indent, count, object.name());
+            }
+        }
+    }
+
+    /// Graphviz description of the book, raw resources are ellipses and products boxes.
+    /// Catalysts are dashed edges and byproducts dotted edges
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph recipes {\n    rankdir=LR;\n");
+        for object in self.objects() {
+            let shape = if self.recipe_for(object).is_some() {
+                "box"
+            } else {
+                "ellipse"
+            };
+            let _ = writeln!(
+                dot,
+                "    \"{:?}\" [label=\"{}\", shape={}];",
+                object,
+                object.name(),
+                shape
+            );
+        }
+        for recipe in self.book.recipes.iter() {
+            for ingredient in recipe.needed.iter() {
+                let _ = writeln!(
+                    dot,
+                    "    \"{:?}\" -> \"{:?}\" [label=\"{}\"];",
+                    WorldObject::Item(ingredient.item),
+                    recipe.produces,
+                    ingredient.count
+                );
+            }
+            for catalyst in recipe.catalysts.iter() {
+                let _ = writeln!(
+                    dot,
+                    "    \"{:?}\" -> \"{:?}\" [label=\"{}\", style=dashed];",
+                    WorldObject::Item(catalyst.item),
+                    recipe.produces,
+                    catalyst.count
+                );
+            }
+            for byproduct in recipe.byproducts.iter() {
+                let _ = writeln!(
+                    dot,
+                    "    \"{:?}\" -> \"{:?}\" [label=\"{}\", style=dotted];",
+                    recipe.produces,
+                    WorldObject::Item(byproduct.item),
+                    byproduct.count
+                );
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(items: &[(ItemType, usize)]) -> Vec<ItemAndCount> {
+        items
+            .iter()
+            .map(|&(item, count)| ItemAndCount { item, count })
+            .collect()
+    }
+
+    fn recipe(needed: &[(ItemType, usize)], produces: WorldObject, count: usize) -> CraftingRecipe {
+        CraftingRecipe {
+            needed: items(needed),
+            catalysts: Vec::new(),
+            produces,
+            count,
+            byproducts: Vec::new(),
+            craft_time: 1.0,
+        }
+    }
+
+    /// Twigs and flint make an axe, which is needed to turn twigs into wood for a campfire
+    fn campfire_book() -> CraftingBook {
+        let mut wood = recipe(&[(ItemType::Twig, 3)], WorldObject::Item(ItemType::Wood), 2);
+        wood.catalysts = items(&[(ItemType::Tool(Tool::Axe), 1)]);
+        CraftingBook {
+            recipes: vec![
+                recipe(
+                    &[(ItemType::Twig, 1), (ItemType::Flint, 1)],
+                    WorldObject::Item(ItemType::Tool(Tool::Axe)),
+                    1,
+                ),
+                wood,
+                recipe(
+                    &[(ItemType::Wood, 3), (ItemType::Flint, 2)],
+                    WorldObject::CampFire,
+                    1,
+                ),
+            ],
+        }
+    }
+
+    /// Grass and wood are each made from the other so neither can ever be crafted
+    fn looping_book() -> CraftingBook {
+        CraftingBook {
+            recipes: vec![
+                recipe(
+                    &[(ItemType::Twig, 1), (ItemType::Wood, 1)],
+                    WorldObject::Item(ItemType::Grass),
+                    1,
+                ),
+                recipe(
+                    &[(ItemType::Grass, 2)],
+                    WorldObject::Item(ItemType::Wood),
+                    1,
+                ),
+                recipe(
+                    &[(ItemType::Twig, 1), (ItemType::Flint, 1)],
+                    WorldObject::Item(ItemType::Tool(Tool::Axe)),
+                    1,
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn raw_cost_rounds_up_to_whole_crafts() {
+        let book = campfire_book();
+        let graph = RecipeGraph::new(&book);
+
+        //3 wood takes 2 crafts of 3 twigs, the axe is a catalyst so it costs nothing
+        assert_eq!(
+            graph.raw_cost(WorldObject::CampFire, 1).ok().unwrap(),
+            items(&[(ItemType::Twig, 6), (ItemType::Flint, 2)])
+        );
+        assert_eq!(
+            graph
+                .raw_cost(WorldObject::Item(ItemType::Wood), 4)
+                .ok()
+                .unwrap(),
+            items(&[(ItemType::Twig, 6)])
+        );
+        assert_eq!(
+            graph
+                .raw_cost(WorldObject::Item(ItemType::Tool(Tool::Axe)), 2)
+                .ok()
+                .unwrap(),
+            items(&[(ItemType::Twig, 2), (ItemType::Flint, 2)])
+        );
+        assert_eq!(
+            graph
+                .raw_cost(WorldObject::Item(ItemType::Twig), 5)
+                .ok()
+                .unwrap(),
+            items(&[(ItemType::Twig, 5)])
+        );
+    }
+
+    #[test]
+    fn raw_cost_errors() {
+        let book = campfire_book();
+        let graph = RecipeGraph::new(&book);
+        assert!(graph.raw_cost(WorldObject::Tree, 1).is_err());
+
+        let book = looping_book();
+        let graph = RecipeGraph::new(&book);
+        assert!(graph
+            .raw_cost(WorldObject::Item(ItemType::Wood), 1)
+            .is_err());
+    }
+
+    #[test]
+    fn zero_count_recipes_do_not_panic() {
+        let book = CraftingBook {
+            recipes: vec![recipe(
+                &[(ItemType::Twig, 1)],
+                WorldObject::Item(ItemType::Wood),
+                0,
+            )],
+        };
+        let graph = RecipeGraph::new(&book);
+        assert!(graph
+            .raw_cost(WorldObject::Item(ItemType::Wood), 1)
+            .is_err());
+        assert!(graph
+            .dependency_tree(WorldObject::Item(ItemType::Wood))
+            .contains("makes nothing"));
+    }
+
+    #[test]
+    fn finds_cycles() {
+        let book = campfire_book();
+        assert!(RecipeGraph::new(&book).cycles().is_empty());
+
+        let book = looping_book();
+        let wood = WorldObject::Item(ItemType::Wood);
+        let grass = WorldObject::Item(ItemType::Grass);
+        assert_eq!(
+            RecipeGraph::new(&book).cycles(),
+            vec![vec![wood, grass, wood]]
+        );
+    }
+
+    #[test]
+    fn finds_unreachable() {
+        let book = campfire_book();
+        assert!(RecipeGraph::new(&book).unreachable().is_empty());
+
+        let book = looping_book();
+        assert_eq!(
+            RecipeGraph::new(&book).unreachable(),
+            vec![
+                WorldObject::Item(ItemType::Grass),
+                WorldObject::Item(ItemType::Wood)
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_unused() {
+        //The axe is only a catalyst but that still counts as used
+        let book = campfire_book();
+        assert_eq!(
+            RecipeGraph::new(&book).unused(),
+            vec![WorldObject::CampFire]
+        );
+
+        let book = looping_book();
+        assert_eq!(
+            RecipeGraph::new(&book).unused(),
+            vec![WorldObject::Item(ItemType::Tool(Tool::Axe))]
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+
+use crate::{
+    inventory::{Container, INVENTORY_SIZE},
+    prelude::*,
+    GameState,
+};
+
+pub struct EquipmentPlugin;
+
+#[derive(Inspectable, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EquipSlot {
+    Hand,
+    Body,
+    Head,
+}
+
+/// Whatever is held and worn, each slot holds a single item taken out of the inventory
+#[derive(Component, Inspectable, Default, Clone, Debug, PartialEq)]
+pub struct Equipment {
+    pub hand: Option<ItemType>,
+    pub body: Option<ItemType>,
+    pub head: Option<ItemType>,
+}
+
+/// Something taken off that didn't fit back in the inventory, along with whatever was
+/// stored in its slots
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dropped {
+    pub item: ItemType,
+    pub contents: Vec<ItemAndCount>,
+}
+
+impl Plugin for EquipmentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Main)
+                .with_system(Self::equip_from_ui)
+                .with_system(Self::apply_stat_modifiers.after(Self::equip_from_ui)),
+        )
+        .register_inspectable::<Equipment>();
+    }
+}
+
+impl EquipSlot {
+    pub const ALL: [EquipSlot; 3] = [EquipSlot::Hand, EquipSlot::Body, EquipSlot::Head];
+}
+
+impl Equipment {
+    /// The tool in the hand slot, if it is holding one
+    pub fn tool(&self) -> Option<Tool> {
+        match self.hand {
+            Some(ItemType::Tool(tool)) => Some(tool),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, slot: EquipSlot) -> Option<ItemType> {
+        match slot {
+            EquipSlot::Hand => self.hand,
+            EquipSlot::Body => self.body,
+            EquipSlot::Head => self.head,
+        }
+    }
+
+    fn slot_mut(&mut self, slot: EquipSlot) -> &mut Option<ItemType> {
+        match slot {
+            EquipSlot::Hand => &mut self.hand,
+            EquipSlot::Body => &mut self.body,
+            EquipSlot::Head => &mut self.head,
+        }
+    }
+
+    /// Everything equipped added together
+    pub fn modifiers(&self) -> StatModifiers {
+        EquipSlot::ALL
+            .iter()
+            .filter_map(|slot| self.get(*slot))
+            .fold(StatModifiers::default(), |total, item| {
+                total.combine(item.modifiers())
+            })
+    }
+
+    /// Slots the inventory should have with everything currently equipped
+    pub fn capacity(&self) -> usize {
+        INVENTORY_SIZE
+            + EquipSlot::ALL
+                .iter()
+                .filter_map(|slot| self.get(*slot))
+                .map(|item| item.extra_slots())
+                .sum::<usize>()
+    }
+
+    /// Moves an item from the inventory into its slot, whatever was there goes back into
+    /// the freed up space or comes back out to be dropped if it doesn't fit
+    pub fn equip(
+        &mut self,
+        inventory: &mut Inventory,
+        item: ItemType,
+    ) -> Result<Option<Dropped>, GameError> {
+        let slot = item.equip_slot().ok_or_else(|| {
+            GameError::new(
+                GameErrorType::EquipFailed,
+                format!("{} can't be equipped", item.name()),
+            )
+        })?;
+
+        inventory.remove(&ItemAndCount { item, count: 1 })?;
+        let dropped = self.unequip(inventory, slot);
+        *self.slot_mut(slot) = Some(item);
+        inventory.resize(self.capacity());
+        Ok(dropped)
+    }
+
+    /// Wears a container picked up from the ground, its contents go back into the slots
+    /// it adds. The slot has to be free so nothing else ends up on the ground
+    pub fn wear(
+        &mut self,
+        inventory: &mut Inventory,
+        item: ItemType,
+        contents: &[ItemAndCount],
+    ) -> Result<(), GameError> {
+        let slot = item.equip_slot().ok_or_else(|| {
+            GameError::new(
+                GameErrorType::EquipFailed,
+                format!("{} can't be equipped", item.name()),
+            )
+        })?;
+        if let Some(worn) = self.get(slot) {
+            return Err(GameError::new(
+                GameErrorType::EquipFailed,
+                format!(
+                    "Take off the {} to pick up a full {}",
+                    worn.name(),
+                    item.name()
+                ),
+            ));
+        }
+
+        let first_new_slot = inventory.capacity();
+        *self.slot_mut(slot) = Some(item);
+        inventory.resize(self.capacity());
+        for (slot, item) in inventory.items[first_new_slot..].iter_mut().zip(contents) {
+            *slot = *item;
+        }
+        Ok(())
+    }
+
+    /// Takes an item off and puts it in the inventory, returning it instead if there is no
+    /// room. Anything that added slots takes their contents with it
+    pub fn unequip(&mut self, inventory: &mut Inventory, slot: EquipSlot) -> Option<Dropped> {
+        let item = self.slot_mut(slot).take()?;
+        let contents = inventory.resize(self.capacity());
+        if contents.is_empty() && inventory.add(&ItemAndCount { item, count: 1 }).is_none() {
+            return None;
+        }
+        Some(Dropped { item, contents })
+    }
+}
+
+impl Dropped {
+    /// Empty items drop like anything else, full ones become a container holding the contents
+    pub fn spawn(
+        self,
+        commands: &mut Commands,
+        graphics: &Graphics,
+        messages: &mut EventWriter<GameMessage>,
+        position: Vec2,
+    ) {
+        messages.send(GameMessage::info(format!(
+            "No room for the {}, dropped it",
+            self.item.name()
+        )));
+        if self.contents.is_empty() {
+            ItemAndCount {
+                item: self.item,
+                count: 1,
+            }
+            .spawn_on_ground(commands, graphics, position);
+            return;
+        }
+        //Padded back out so there is room to put things in while it lies there
+        let mut items = self.contents;
+        items.resize(
+            items.len().max(self.item.extra_slots()),
+            ItemAndCount::default(),
+        );
+        let container = WorldObject::Item(self.item).spawn(commands, graphics, position);
+        commands.entity(container).insert(Container { items });
+    }
+}
+
+impl EquipmentPlugin {
+    /// Clicking an inventory item or pressing its hotbar key equips it, clicking an
+    /// equipped item takes it off
+    fn equip_from_ui(
+        mut commands: Commands,
+        mut event_reader: EventReader<UIEvent>,
+        actions: Res<ActionState>,
+        graphics: Res<Graphics>,
+        mut messages: EventWriter<GameMessage>,
+        mut query: Query<(&Transform, &mut Inventory, &mut Equipment), With<Player>>,
+    ) {
+        let (transform, mut inventory, mut equipment) = query.single_mut();
+
+        let mut to_equip = Vec::new();
+        for ev in event_reader.iter() {
+            match ev.0 {
+                UIEventType::InventoryEvent(_, item) => to_equip.push(item.item),
+                UIEventType::EquipmentEvent(slot, _) => {
+                    //A full inventory drops it at the players feet rather than losing it
+                    if let Some(dropped) = equipment.unequip(&mut inventory, slot) {
+                        dropped.spawn(
+                            &mut commands,
+                            &graphics,
+                            &mut messages,
+                            transform.translation.truncate(),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+        for slot in actions.hotbar_just_pressed() {
+            if let Some(item) = inventory.items.get(slot) {
+                to_equip.push(item.item);
+            }
+        }
+
+        for item in to_equip {
+            if item.equip_slot().is_none() {
+                continue;
+            }
+            match equipment.equip(&mut inventory, item) {
+                Ok(Some(dropped)) => dropped.spawn(
+                    &mut commands,
+                    &graphics,
+                    &mut messages,
+                    transform.translation.truncate(),
+                ),
+                Ok(None) => {}
+                Err(e) => messages.send(e.into()),
+            }
+        }
+    }
+
+    fn apply_stat_modifiers(
+        mut commands: Commands,
+        mut query: Query<
+            (
+                Entity,
+                &Equipment,
+                Option<&mut Insulation>,
+                Option<&mut SpeedModifiers>,
+            ),
+            Changed<Equipment>,
+        >,
+    ) {
+        for (entity, equipment, insulation, speed) in query.iter_mut() {
+            let modifiers = equipment.modifiers();
+            match insulation {
+                Some(mut insulation) => insulation.0 = modifiers.insulation,
+                None => {
+                    commands
+                        .entity(entity)
+                        .insert(Insulation(modifiers.insulation));
+                }
+            }
+            if let Some(mut speed) = speed {
+                speed.equipment = modifiers.speed;
+            }
+        }
+    }
+}
@@ -21,6 +21,11 @@ impl GameError {
 pub enum GameErrorType {
     ItemMissing,
     CraftingFailed,
+    InvalidRecipe,
+    AssetLoadFailed,
+    InvalidGraphics,
+    SaveFailed,
+    EquipFailed,
 }
 
 impl fmt::Display for GameError {
@@ -28,3 +33,5 @@ impl fmt::Display for GameError {
         write!(f, "{:?}: {}", self.error_type, self.error_payload)
     }
 }
+
+impl std::error::Error for GameError {}
@@ -1,11 +1,16 @@
 use crate::prelude::{Player, TILE_SIZE};
-use crate::{GameState, HEIGHT, RESOLUTION};
+use crate::{GameState, HEIGHT};
 use bevy::prelude::*;
-use bevy::render::camera::{Camera2d, ScalingMode};
+use bevy::render::camera::{Camera2d, CameraProjection, ScalingMode};
+use bevy::window::WindowResized;
 use bevy_inspector_egui::{Inspectable, RegisterInspectable};
 
 pub struct GameCameraPlugin;
 
+/// Tiles visible from the top to the bottom of the window whatever its size, wider windows
+/// see more to the sides instead of zooming in
+pub const VIEW_HEIGHT: f32 = HEIGHT / TILE_SIZE;
+
 /// Marks something that should always be in a constant place on screen,
 /// Currently only used for the campfire overlay but there are probably better
 /// ways of handling this
@@ -25,27 +30,49 @@ impl Plugin for GameCameraPlugin {
                 .with_system(Self::camera_follow.after(Self::camera_follows_player))
                 .with_system(Self::camera_follows_player),
         )
+        //Resize events only live for two frames, so they are read even while paused
+        .add_system(Self::resize_camera)
         .register_inspectable::<CameraFollower>();
     }
 }
 
 impl GameCameraPlugin {
-    fn spawn_camera(mut commands: Commands) {
+    fn spawn_camera(mut commands: Commands, windows: Res<Windows>) {
         //commands.spawn_bundle(UiCameraBundle::default());
 
         let mut camera = OrthographicCameraBundle::new_2d();
 
-        // One unit in world space is one tile
-        camera.orthographic_projection.left = -HEIGHT / TILE_SIZE / 2.0 * RESOLUTION;
-        camera.orthographic_projection.right = HEIGHT / TILE_SIZE / 2.0 * RESOLUTION;
-        camera.orthographic_projection.top = HEIGHT / TILE_SIZE / 2.0;
-        camera.orthographic_projection.bottom = -HEIGHT / TILE_SIZE / 2.0;
         camera.orthographic_projection.scaling_mode = ScalingMode::None;
+        if let Some(window) = windows.get_primary() {
+            fit_projection(
+                &mut camera.orthographic_projection,
+                window.width(),
+                window.height(),
+            );
+        }
 
         commands.spawn_bundle(camera);
         info!("camera spawned")
     }
 
+    /// ScalingMode::None leaves the projection alone so it has to follow the window here
+    fn resize_camera(
+        mut resized: EventReader<WindowResized>,
+        windows: Res<Windows>,
+        mut camera_query: Query<(&mut OrthographicProjection, &mut Camera), With<Camera2d>>,
+    ) {
+        let primary = match windows.get_primary() {
+            Some(window) => window.id(),
+            None => return,
+        };
+        if let Some(resize) = resized.iter().filter(|ev| ev.id == primary).last() {
+            for (mut projection, mut camera) in camera_query.iter_mut() {
+                fit_projection(&mut projection, resize.width, resize.height);
+                camera.projection_matrix = projection.get_projection_matrix();
+            }
+        }
+    }
+
     fn camera_follow(
         mut follower_query: Query<(&mut Transform, &CameraFollower)>,
         camera_query: Query<&Transform, (With<Camera2d>, Without<CameraFollower>)>,
@@ -69,3 +96,12 @@ impl GameCameraPlugin {
         camera_transform.translation.y = player_transform.y;
     }
 }
+
+/// One unit in world space is one tile, VIEW_HEIGHT of them fit the window vertically
+fn fit_projection(projection: &mut OrthographicProjection, width: f32, height: f32) {
+    let aspect = if height > 0.0 { width / height } else { 1.0 };
+    projection.left = -VIEW_HEIGHT / 2.0 * aspect;
+    projection.right = VIEW_HEIGHT / 2.0 * aspect;
+    projection.top = VIEW_HEIGHT / 2.0;
+    projection.bottom = -VIEW_HEIGHT / 2.0;
+}
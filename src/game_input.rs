@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+pub struct GameInputPlugin;
+
+/// Where the players bindings are stored, created with the defaults if missing
+pub const BINDINGS_PATH: &str = "config/bindings.ron";
+
+/// Sticks report tiny values when at rest, anything below this is ignored
+const STICK_DEADZONE: f32 = 0.2;
+
+/// Everything the player can do, gameplay systems read these instead of raw inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum InputAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Interact,
+    Attack,
+    Place,
+    Cancel,
+    /// Held while crafting to craft as many as possible
+    CraftMany,
+    /// Opens and closes the pause menu
+    Pause,
+    /// Selects an inventory slot, 0 indexed
+    Hotbar(usize),
+}
+
+/// A single physical input that can trigger an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+}
+
+impl InputBinding {
+    /// Short readable form for the settings screen
+    pub fn name(&self) -> String {
+        match self {
+            InputBinding::Key(key) => format!("{:?}", key),
+            InputBinding::Mouse(button) => format!("Mouse {:?}", button),
+            InputBinding::GamepadButton(button) => format!("Pad {:?}", button),
+        }
+    }
+}
+
+/// Resource mapping actions to any number of bindings, loaded from BINDINGS_PATH
+/// and changeable at runtime with rebind
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub bindings: BTreeMap<InputAction, Vec<InputBinding>>,
+    /// Move actions are also driven by this stick
+    pub movement_stick: (GamepadAxisType, GamepadAxisType),
+}
+
+/// The result of reading every binding this frame, the only input gameplay should look at
+#[derive(Default, Debug)]
+pub struct ActionState {
+    pressed: HashSet<InputAction>,
+    just_pressed: HashSet<InputAction>,
+    just_released: HashSet<InputAction>,
+    /// Consumed actions stay hidden until their input is let go, otherwise a held click
+    /// would show up as a fresh press on the next frame
+    consumed: HashSet<InputAction>,
+    movement: Vec2,
+}
+
+/// Whether the cursor was over a HUD widget, kept up to date by the UI so this layer doesn't
+/// need to know about it
+#[derive(Default, Debug)]
+pub struct UIHitTest {
+    pub over_ui: bool,
+}
+
+impl Plugin for GameInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputBindings::load_or_default(BINDINGS_PATH))
+            .init_resource::<ActionState>()
+            .init_resource::<UIHitTest>()
+            .add_system_to_stage(CoreStage::PreUpdate, update_action_state.after(InputSystem))
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                capture_ui_clicks.after(update_action_state),
+            );
+    }
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use InputAction::*;
+        use InputBinding::*;
+
+        let mut bindings = BTreeMap::new();
+        bindings.insert(MoveUp, vec![Key(KeyCode::W), Key(KeyCode::Up)]);
+        bindings.insert(MoveDown, vec![Key(KeyCode::S), Key(KeyCode::Down)]);
+        bindings.insert(MoveLeft, vec![Key(KeyCode::A), Key(KeyCode::Left)]);
+        bindings.insert(MoveRight, vec![Key(KeyCode::D), Key(KeyCode::Right)]);
+        bindings.insert(
+            Interact,
+            vec![Key(KeyCode::Space), GamepadButton(GamepadButtonType::South)],
+        );
+        bindings.insert(
+            Attack,
+            vec![Key(KeyCode::F), GamepadButton(GamepadButtonType::West)],
+        );
+        bindings.insert(
+            Place,
+            vec![
+                Mouse(MouseButton::Left),
+                GamepadButton(GamepadButtonType::RightTrigger),
+            ],
+        );
+        bindings.insert(
+            Cancel,
+            vec![Key(KeyCode::Escape), GamepadButton(GamepadButtonType::East)],
+        );
+        bindings.insert(
+            CraftMany,
+            vec![
+                Key(KeyCode::LShift),
+                Key(KeyCode::RShift),
+                GamepadButton(GamepadButtonType::LeftTrigger),
+            ],
+        );
+        bindings.insert(
+            Pause,
+            vec![Key(KeyCode::P), GamepadButton(GamepadButtonType::Start)],
+        );
+        let hotbar_keys = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+        ];
+        for (slot, key) in hotbar_keys.into_iter().enumerate() {
+            bindings.insert(Hotbar(slot), vec![Key(key)]);
+        }
+
+        Self {
+            bindings,
+            movement_stick: (GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+        }
+    }
+}
+
+impl InputBindings {
+    /// Reads the bindings file, falling back to (and writing out) the defaults if it
+    /// is missing or broken. Actions added since the file was written get their defaults
+    pub fn load_or_default(path: &str) -> Self {
+        let loaded = fs::read_to_string(path)
+            .map_err(|e| {
+                GameError::new(
+                    GameErrorType::AssetLoadFailed,
+                    format!("Failed to read {}: {}", path, e),
+                )
+            })
+            .and_then(|desc| {
+                ron::de::from_str::<InputBindings>(&desc).map_err(|e| {
+                    GameError::new(
+                        GameErrorType::AssetLoadFailed,
+                        format!("Failed to parse {}: {}", path, e),
+                    )
+                })
+            });
+
+        match loaded {
+            Ok(mut bindings) => {
+                for (action, defaults) in InputBindings::default().bindings {
+                    bindings.bindings.entry(action).or_insert(defaults);
+                }
+                bindings
+            }
+            Err(e) => {
+                warn!("{}, using default bindings", e);
+                let bindings = InputBindings::default();
+                if !Path::new(path).exists() {
+                    if let Err(e) = bindings.save(path) {
+                        warn!("{}", e);
+                    }
+                }
+                bindings
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), GameError> {
+        let to_error =
+            |e: String| GameError::new(GameErrorType::AssetLoadFailed, format!("{}: {}", path, e));
+        let desc = ron::ser::to_string_pretty(self, PrettyConfig::default())
+            .map_err(|e| to_error(e.to_string()))?;
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent).map_err(|e| to_error(e.to_string()))?;
+        }
+        fs::write(path, desc).map_err(|e| to_error(e.to_string()))
+    }
+
+    /// Replaces every binding of an action, removing the input from any other action
+    /// so one key never triggers two things
+    pub fn rebind(&mut self, action: InputAction, binding: InputBinding) {
+        for bindings in self.bindings.values_mut() {
+            bindings.retain(|existing| *existing != binding);
+        }
+        self.bindings.insert(action, vec![binding]);
+    }
+
+    /// Adds another input to an action without touching its existing bindings
+    pub fn add_binding(&mut self, action: InputAction, binding: InputBinding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn bindings_for(&self, action: InputAction) -> &[InputBinding] {
+        self.bindings
+            .get(&action)
+            .map(|bindings| bindings.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: InputAction) -> bool {
+        self.just_released.contains(&action)
+    }
+
+    /// Combined keyboard and stick direction, each axis is in -1..1
+    pub fn movement(&self) -> Vec2 {
+        self.movement
+    }
+
+    /// Every hotbar slot selected this frame
+    pub fn hotbar_just_pressed(&self) -> impl Iterator<Item = usize> + '_ {
+        self.just_pressed.iter().filter_map(|action| match action {
+            InputAction::Hotbar(slot) => Some(*slot),
+            _ => None,
+        })
+    }
+
+    /// Lets other systems consume an action, for example when the UI already handled a click
+    pub fn consume(&mut self, action: InputAction) {
+        self.consumed.insert(action);
+        self.pressed.remove(&action);
+        self.just_pressed.remove(&action);
+    }
+}
+
+/// Reads all bound inputs once per frame and converts them into actions
+fn update_action_state(
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    bindings: Res<InputBindings>,
+    mut actions: ResMut<ActionState>,
+) {
+    let previously_pressed = std::mem::take(&mut actions.pressed);
+
+    for (action, action_bindings) in bindings.bindings.iter() {
+        let pressed = action_bindings.iter().any(|binding| match binding {
+            InputBinding::Key(key) => keyboard.pressed(*key),
+            InputBinding::Mouse(button) => mouse.pressed(*button),
+            InputBinding::GamepadButton(button_type) => gamepads
+                .iter()
+                .any(|gamepad| gamepad_buttons.pressed(GamepadButton(*gamepad, *button_type))),
+        });
+        if !pressed {
+            actions.consumed.remove(action);
+        } else if !actions.consumed.contains(action) {
+            actions.pressed.insert(*action);
+        }
+    }
+
+    actions.just_pressed = actions
+        .pressed
+        .difference(&previously_pressed)
+        .copied()
+        .collect();
+    actions.just_released = previously_pressed
+        .difference(&actions.pressed)
+        .copied()
+        .collect();
+
+    let mut movement = Vec2::ZERO;
+    if actions.pressed(InputAction::MoveLeft) {
+        movement.x -= 1.0;
+    }
+    if actions.pressed(InputAction::MoveRight) {
+        movement.x += 1.0;
+    }
+    if actions.pressed(InputAction::MoveUp) {
+        movement.y += 1.0;
+    }
+    if actions.pressed(InputAction::MoveDown) {
+        movement.y -= 1.0;
+    }
+
+    let (x_axis, y_axis) = bindings.movement_stick;
+    for gamepad in gamepads.iter() {
+        let stick = Vec2::new(
+            gamepad_axes
+                .get(GamepadAxis(*gamepad, x_axis))
+                .unwrap_or(0.0),
+            gamepad_axes
+                .get(GamepadAxis(*gamepad, y_axis))
+                .unwrap_or(0.0),
+        );
+        if stick.length() > STICK_DEADZONE {
+            movement += stick;
+        }
+    }
+
+    actions.movement = movement.clamp(Vec2::splat(-1.0), Vec2::splat(1.0));
+}
+
+/// Clicks on the HUD belong to the UI, any action they started is consumed so gameplay
+/// systems never see them
+fn capture_ui_clicks(
+    hit_test: Res<UIHitTest>,
+    mouse: Res<Input<MouseButton>>,
+    bindings: Res<InputBindings>,
+    mut actions: ResMut<ActionState>,
+) {
+    if !hit_test.over_ui {
+        return;
+    }
+    for (action, action_bindings) in bindings.bindings.iter() {
+        let clicked = action_bindings.iter().any(|binding| match binding {
+            InputBinding::Mouse(button) => mouse.just_pressed(*button),
+            _ => false,
+        });
+        if clicked && actions.just_pressed(*action) {
+            actions.consume(*action);
+        }
+    }
+}
@@ -1,7 +1,13 @@
 use crate::{
+    assets::FontAssets,
+    calendar::Season,
+    equipment::{EquipSlot, Equipment},
+    game_input::UIHitTest,
     item::{ItemAndCount, ItemType, WorldObject},
-    prelude::{HandUI, InventoryUI, RecipeUI},
-    GameState, HEIGHT, RESOLUTION,
+    menus::{MenuUI, UIMenu},
+    messages::{MessageLogUI, UIMessages},
+    prelude::{ClockUI, ContainerUI, EquipmentUI, Inventory, InventoryUI, RecipeTooltip, RecipeUI},
+    GameState,
 };
 use bevy::prelude::*;
 use kayak_ui::{
@@ -9,9 +15,9 @@ use kayak_ui::{
     core::{
         bind, render, rsx,
         styles::{Edge, LayoutType, PositionType, Style as KayakStyle, StyleProp, Units},
-        widget, Color, WidgetProps,
+        widget, Binding, Bound, Color, MutableBound, WidgetProps,
     },
-    widgets::{App, Background, Clip},
+    widgets::{App, Background, Clip, Element},
 };
 
 pub struct GameUIPlugin;
@@ -24,14 +30,26 @@ pub struct UIEvent(pub UIEventType);
 pub enum UIEventType {
     None,
     CraftEvent(WorldObject),
-    ToolEvent(ItemAndCount),
-    InventoryEvent(ItemAndCount),
+    EquipmentEvent(EquipSlot, ItemAndCount),
+    /// Clicking an inventory slot, which can be empty
+    InventoryEvent(usize, ItemAndCount),
+    /// Dragging the contents of one slot onto another
+    MoveEvent(SlotRef, SlotRef),
+}
+
+/// Somewhere an item can be dragged from or dropped on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlotRef {
+    Inventory(usize),
+    Equipment(EquipSlot),
+    /// A slot in whichever container is open
+    Container(usize),
 }
 
 impl UIEventType {
     pub fn item_and_count(self) -> ItemAndCount {
         match self {
-            UIEventType::None => ItemAndCount {
+            UIEventType::None | UIEventType::MoveEvent(..) => ItemAndCount {
                 item: ItemType::None,
                 count: 0,
             },
@@ -39,7 +57,7 @@ impl UIEventType {
                 item: ItemType::None,
                 count: 1,
             },
-            UIEventType::ToolEvent(i) | UIEventType::InventoryEvent(i) => i,
+            UIEventType::EquipmentEvent(_, i) | UIEventType::InventoryEvent(_, i) => i,
         }
     }
 }
@@ -52,9 +70,64 @@ impl Default for UIEventType {
 
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct UIItems {
-    pub inventory_items: Vec<ItemAndCount>,
+    /// Snapshot of the player inventory, every slot is drawn including the empty ones
+    pub inventory: Inventory,
+    /// Slot last picked with the hotbar or a click
+    pub selected: Option<usize>,
+    /// Slots of the container next to the player, if there is one
+    pub container: Option<Vec<ItemAndCount>>,
     pub crafting_items: Vec<WorldObject>,
-    pub hand_item: Option<ItemAndCount>,
+    pub equipment: Equipment,
+    /// The recipe currently being crafted and how far along it is
+    pub crafting_progress: Option<(WorldObject, f32)>,
+}
+
+/// The day and season shown in the corner of the screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct UIClock {
+    /// Counted from 1 for the player
+    pub day: u32,
+    pub season: Season,
+    pub night: bool,
+}
+
+impl Default for UIClock {
+    fn default() -> Self {
+        Self {
+            day: 1,
+            season: Season::Spring,
+            night: false,
+        }
+    }
+}
+
+/// The UI item currently under the cursor, drives the recipe tooltip
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct UIHover(pub Option<UIEventType>);
+
+/// The slot the mouse was pressed on, dropped wherever it is released
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct UIDrag(pub Option<SlotRef>);
+
+/// Multiplier on the size of the HUD, bound so the layout is rebuilt when it changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UIScale(pub f32);
+
+/// Player adjustable HUD options
+#[derive(Debug, Clone)]
+pub struct UISettings {
+    pub scale: f32,
+}
+
+impl Default for UISettings {
+    fn default() -> Self {
+        Self { scale: 1.0 }
+    }
+}
+
+impl UISettings {
+    pub const MIN_SCALE: f32 = 0.5;
+    pub const MAX_SCALE: f32 = 2.5;
 }
 
 #[derive(Default, Debug, WidgetProps, Clone, PartialEq)]
@@ -66,98 +139,178 @@ pub struct UIProps {
 /// Main root widget for our game UI
 #[widget]
 fn GameUI() {
+    //Nothing to show until the game has started
+    let ui_menu = context.query_world::<Res<Binding<UIMenu>>, _, _>(|menu| menu.clone());
+    context.bind(&ui_menu);
+    let in_game = ui_menu.get().in_game;
+
+    //Panels are anchored to the edges of the window so they stay put whatever its size
+    let scale = context.query_world::<Res<Binding<UIScale>>, _, _>(|scale| scale.clone());
+    context.bind(&scale);
+    let scale = scale.get().0;
+    let px = |pixels: f32| StyleProp::Value(Units::Pixels(pixels * scale));
+    let stretch = || StyleProp::Value(Units::Stretch(1.0));
+    let auto = || StyleProp::Value(Units::Auto);
+
     let row_style = KayakStyle {
         layout_type: StyleProp::Value(LayoutType::Row),
         padding: StyleProp::Value(Edge::all(Units::Stretch(1.0))),
-        col_between: StyleProp::Value(Units::Pixels(10.)),
+        col_between: px(10.),
+        width: auto(),
+        height: auto(),
         ..Default::default()
     };
 
     let column_style = KayakStyle {
         //padding: StyleProp::Value(Edge::axis(Units::Pixels(10.), Units::Stretch(1.0))),
         padding: StyleProp::Value(Edge::all(Units::Stretch(1.0))),
-        row_between: StyleProp::Value(Units::Pixels(10.)),
+        row_between: px(10.),
+        width: auto(),
+        height: auto(),
         ..Default::default()
     };
 
     let clip_styles = KayakStyle {
-        padding: StyleProp::Value(Edge::all(Units::Pixels(5.0))),
+        padding: StyleProp::Value(Edge::all(Units::Pixels(5.0 * scale))),
         width: StyleProp::Value(Units::Stretch(1.0)),
         height: StyleProp::Value(Units::Stretch(1.0)),
         ..Default::default()
     };
 
-    let width = 100.;
-    let inventory_pos = (HEIGHT * RESOLUTION / 2., HEIGHT - width);
-    let recipe_pos = (0., HEIGHT / 2.0);
-    let hand_pos = (HEIGHT * RESOLUTION - width, HEIGHT - width);
+    let margin = 10.;
+    //Room for the recipe column before the tooltip starts
+    let recipe_width = 70.;
 
     let recipe_style = KayakStyle {
         position_type: StyleProp::Value(PositionType::SelfDirected),
-        left: StyleProp::Value(Units::Pixels(recipe_pos.0)),
-        top: StyleProp::Value(Units::Pixels(recipe_pos.1)),
-        //XXX these sizes should not matter ...
-        width: StyleProp::Value(Units::Pixels(width)),
-        height: StyleProp::Value(Units::Pixels(width)),
-        max_width: StyleProp::Value(Units::Pixels(width)),
-        max_height: StyleProp::Value(Units::Pixels(width)),
+        left: px(margin),
+        top: stretch(),
+        bottom: stretch(),
+        width: auto(),
+        height: auto(),
         background_color: StyleProp::Value(Color::new(0.7, 0.4, 0.4, 0.0)),
         ..Default::default()
     };
 
     let inventory_style = KayakStyle {
         position_type: StyleProp::Value(PositionType::SelfDirected),
-        left: StyleProp::Value(Units::Pixels(inventory_pos.0)),
-        top: StyleProp::Value(Units::Pixels(inventory_pos.1)),
-        width: StyleProp::Value(Units::Pixels(width)),
-        height: StyleProp::Value(Units::Pixels(width)),
-        max_width: StyleProp::Value(Units::Pixels(width)),
-        max_height: StyleProp::Value(Units::Pixels(width)),
+        left: stretch(),
+        right: stretch(),
+        bottom: px(margin),
+        width: auto(),
+        height: auto(),
         background_color: StyleProp::Value(Color::new(0.4, 0.7, 0.4, 0.0)),
         ..Default::default()
     };
 
-    let hand_style = KayakStyle {
+    let tooltip_style = KayakStyle {
         position_type: StyleProp::Value(PositionType::SelfDirected),
-        left: StyleProp::Value(Units::Pixels(hand_pos.0)),
-        top: StyleProp::Value(Units::Pixels(hand_pos.1)),
-        width: StyleProp::Value(Units::Pixels(width)),
-        height: StyleProp::Value(Units::Pixels(width)),
-        max_width: StyleProp::Value(Units::Pixels(width)),
-        max_height: StyleProp::Value(Units::Pixels(width)),
+        left: px(margin + recipe_width),
+        top: stretch(),
+        bottom: stretch(),
+        width: auto(),
+        height: auto(),
+        ..Default::default()
+    };
+
+    let clock_style = KayakStyle {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        left: stretch(),
+        right: px(margin),
+        top: px(margin),
+        width: px(190.),
+        height: auto(),
+        ..Default::default()
+    };
+
+    //Sits just above the inventory so items can be dragged straight across
+    let container_style = KayakStyle {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        left: stretch(),
+        right: stretch(),
+        bottom: px(margin + 70.),
+        width: auto(),
+        height: auto(),
+        ..Default::default()
+    };
+
+    //Above the recipe column, out of the way of everything else
+    let message_style = KayakStyle {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        left: px(margin),
+        top: px(margin),
+        width: auto(),
+        height: auto(),
+        ..Default::default()
+    };
+
+    let equipment_style = KayakStyle {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        left: stretch(),
+        right: px(margin),
+        top: stretch(),
+        bottom: px(margin),
+        width: auto(),
+        height: auto(),
         background_color: StyleProp::Value(Color::new(0.4, 0.4, 0.7, 0.0)),
         ..Default::default()
     };
 
-    rsx! {
-        <Clip styles= {Some(clip_styles)}>
-            <Background styles={Some(inventory_style)}>
-                <InventoryUI styles={Some(row_style)} />
-            </Background>
-            <Background styles={Some(recipe_style)}>
-                <RecipeUI styles={Some(column_style)} />
-            </Background>
-            <Background styles={Some(hand_style)}>
-                <HandUI styles={Some(row_style)} />
-            </Background>
-        </Clip>
+    if in_game {
+        rsx! {
+            <Clip styles= {Some(clip_styles)}>
+                <Background styles={Some(inventory_style)}>
+                    <InventoryUI styles={Some(row_style)} />
+                </Background>
+                <Element styles={Some(container_style)}>
+                    <ContainerUI styles={Some(row_style)} />
+                </Element>
+                <Background styles={Some(recipe_style)}>
+                    <RecipeUI styles={Some(column_style)} />
+                </Background>
+                <Element styles={Some(tooltip_style)}>
+                    <RecipeTooltip />
+                </Element>
+                <Background styles={Some(equipment_style)}>
+                    <EquipmentUI styles={Some(row_style)} />
+                </Background>
+                <Element styles={Some(clock_style)}>
+                    <ClockUI />
+                </Element>
+                <Element styles={Some(message_style)}>
+                    <MessageLogUI />
+                </Element>
+            </Clip>
+        }
+    } else {
+        rsx! {
+            <Element />
+        }
     }
 }
 
 fn setup_game_ui(
     mut commands: Commands,
     mut font_mapping: ResMut<FontMapping>,
-    asset_server: Res<AssetServer>,
+    fonts: Res<FontAssets>,
+    settings: Res<UISettings>,
 ) {
     commands.spawn_bundle(UICameraBundle::new());
-    font_mapping.set_default(asset_server.load("roboto.kayak_font"));
+    font_mapping.set_default(fonts.ui_font.clone());
 
     commands.insert_resource(bind(UIItems::default()));
+    commands.insert_resource(bind(UIHover::default()));
+    commands.insert_resource(bind(UIDrag::default()));
+    commands.insert_resource(bind(UIClock::default()));
+    commands.insert_resource(bind(UIScale(settings.scale)));
+    commands.insert_resource(bind(UIMenu::default()));
+    commands.insert_resource(bind(UIMessages::default()));
 
     let context = BevyContext::new(|context| {
         render! {
             <App>
                 <GameUI />
+                <MenuUI />
             </App>
         }
     });
@@ -165,10 +318,45 @@ fn setup_game_ui(
     commands.insert_resource(context);
 }
 
+/// Kayak has already handled this frame's input by now, the result is used by the next
+fn update_ui_hit_test(context: Option<Res<BevyContext>>, mut hit_test: ResMut<UIHitTest>) {
+    let over_ui = context.map_or(false, |context| context.contains_cursor());
+    if hit_test.over_ui != over_ui {
+        hit_test.over_ui = over_ui;
+    }
+}
+
+/// Releasing the mouse away from any slot cancels the drag, slots handle their own drops
+/// before this runs
+fn clear_drag(mouse: Res<Input<MouseButton>>, drag: Option<Res<Binding<UIDrag>>>) {
+    if let Some(drag) = drag {
+        if mouse.just_released(MouseButton::Left) && drag.get().0.is_some() {
+            drag.set(UIDrag(None));
+        }
+    }
+}
+
+/// Passes changes to the settings on to the bound scale the widgets read
+fn update_ui_scale(settings: Res<UISettings>, scale: Option<Res<Binding<UIScale>>>) {
+    if let Some(scale) = scale {
+        let value = settings
+            .scale
+            .clamp(UISettings::MIN_SCALE, UISettings::MAX_SCALE);
+        if settings.is_changed() && scale.get().0 != value {
+            scale.set(UIScale(value));
+        }
+    }
+}
+
 impl Plugin for GameUIPlugin {
     fn build(&self, app: &mut bevy::app::App) {
         app.add_plugin(BevyKayakUIPlugin)
-            .add_system_set(SystemSet::on_enter(GameState::Main).with_system(setup_game_ui))
+            .init_resource::<UISettings>()
+            //The menus are kayak widgets too so the context is made before the game starts
+            .add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(setup_game_ui))
+            .add_system(update_ui_scale)
+            .add_system_to_stage(CoreStage::Last, clear_drag)
+            .add_system_to_stage(CoreStage::Last, update_ui_hit_test)
             .add_event::<UIEvent>();
     }
 }
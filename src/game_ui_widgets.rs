@@ -4,16 +4,17 @@ use kayak_ui::{
     bevy::ImageManager,
     core::{
         constructor, rsx,
-        styles::{Edge, Style, StyleProp, Units},
-        widget, Binding, Bound, Color, EventType, OnEvent, VecTracker, WidgetProps,
+        styles::{Edge, LayoutType, PositionType, Style, StyleProp, Units},
+        widget, Binding, Bound, Color, EventType, MutableBound, OnEvent, VecTracker, WidgetProps,
     },
-    widgets::{Button, Element, Image, Text},
+    widgets::{Background, Button, Element, Image, Text},
 };
 
 use crate::{
-    game_ui::{UIItems, UIProps},
+    crafting::CraftingBook,
+    game_ui::{UIClock, UIDrag, UIHover, UIItems, UIProps, UIScale},
     item::WorldObject,
-    prelude::{Graphics, UIEvent, UIEventType},
+    prelude::{EquipSlot, Graphics, ItemAndCount, SlotRef, UIEvent, UIEventType},
 };
 
 #[derive(Default, Debug, WidgetProps, Clone, PartialEq)]
@@ -24,52 +25,191 @@ pub struct ItemProps {
     #[prop_field(Styles)]
     pub styles: Option<Style>,
     pub disabled: bool,
+    /// Fraction of the current craft that is done, drawn as a bar over the icon
+    pub progress: Option<f32>,
+    /// Set on slots items can be dragged out of and dropped into
+    pub slot: Option<SlotRef>,
+    pub selected: bool,
 }
 
 #[widget]
 pub fn Item(props: ItemProps) {
+    let scale = context.query_world::<Res<Binding<UIScale>>, _, _>(|scale| scale.clone());
+    context.bind(&scale);
+    let scale = scale.get().0;
+
+    let slot_style = Style {
+        width: StyleProp::Value(Units::Pixels(50.0 * scale)),
+        height: StyleProp::Value(Units::Pixels(50.0 * scale)),
+        ..props.styles.clone().unwrap_or_default()
+    };
+
     let button_style = Style {
-        width: StyleProp::Value(Units::Pixels(50.0)),
-        height: StyleProp::Value(Units::Pixels(50.0)),
+        width: StyleProp::Value(Units::Stretch(1.0)),
+        height: StyleProp::Value(Units::Stretch(1.0)),
         background_color: StyleProp::Value(Color::TRANSPARENT),
         //background_color: StyleProp::Value(Color::new(0.4, 0.4, 0.4, 1.0)),
         padding: StyleProp::Value(Edge::all(Units::Stretch(1.0))),
-        ..props.styles.clone().unwrap_or_default()
+        ..default()
     };
 
     let image_style = Style {
-        width: StyleProp::Value(Units::Pixels(45.0)),
-        height: StyleProp::Value(Units::Pixels(45.0)),
+        width: StyleProp::Value(Units::Pixels(45.0 * scale)),
+        height: StyleProp::Value(Units::Pixels(45.0 * scale)),
+        ..default()
+    };
+
+    let box_style = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        left: StyleProp::Value(Units::Pixels(0.0)),
+        top: StyleProp::Value(Units::Pixels(0.0)),
+        width: StyleProp::Value(Units::Stretch(1.0)),
+        height: StyleProp::Value(Units::Stretch(1.0)),
         ..default()
     };
 
     let ui_event = props.event_type;
+    let slot = props.slot;
 
-    let on_click_event = OnEvent::new(move |context, event| {
-        if let EventType::Click(..) = event.event_type {
-            context.query_world::<EventWriter<UIEvent>, _, _>(move |mut ev| {
-                ev.send(UIEvent(ui_event));
-            });
+    //Clicks land on the slot the mouse is released over, so a release after dragging
+    //from somewhere else is a drop instead
+    let on_click_event = OnEvent::new(move |context, event| match event.event_type {
+        EventType::MouseDown(..) => {
+            if let Some(slot) = slot {
+                context.query_world::<Res<Binding<UIDrag>>, _, _>(move |drag| {
+                    drag.set(UIDrag(Some(slot)));
+                });
+            }
+        }
+        EventType::Click(..) => {
+            context.query_world::<(Res<Binding<UIDrag>>, EventWriter<UIEvent>), _, _>(
+                move |(drag, mut ev)| {
+                    let dragged = drag.get().0;
+                    if dragged.is_some() {
+                        drag.set(UIDrag(None));
+                    }
+                    match (dragged, slot) {
+                        (Some(from), Some(to)) if from != to => {
+                            ev.send(UIEvent(UIEventType::MoveEvent(from, to)))
+                        }
+                        _ => ev.send(UIEvent(ui_event)),
+                    }
+                },
+            );
         }
+        _ => {}
     });
 
-    let handle = context.query_world::<ResMut<ImageManager>, _, _>(|mut manager| {
-        manager.get(&props.handle.clone().unwrap())
+    //Disabled buttons drop every event, the tooltip still has to show why a recipe
+    //can't be crafted so hovering is tracked on the slot around the button
+    let on_hover_event = OnEvent::new(move |context, event| match event.event_type {
+        EventType::MouseIn(..) => {
+            context.query_world::<Res<Binding<UIHover>>, _, _>(move |hover| {
+                hover.set(UIHover(Some(ui_event)));
+            });
+        }
+        EventType::MouseOut(..) => {
+            //Only clear the hover if another item hasn't already claimed it
+            context.query_world::<Res<Binding<UIHover>>, _, _>(move |hover| {
+                if hover.get().0 == Some(ui_event) {
+                    hover.set(UIHover(None));
+                }
+            });
+        }
+        _ => {}
     });
 
-    let item_count = format!("x{}", props.clone().event_type.item_and_count().count);
+    let (handle, box_handle) = context.query_world::<(ResMut<ImageManager>, Res<Graphics>), _, _>(
+        |(mut manager, graphics)| {
+            (
+                manager.get(&props.handle.clone().unwrap()),
+                manager.get(&graphics.box_image),
+            )
+        },
+    );
+
+    //Single items don't need a count
+    let count = props.clone().event_type.item_and_count().count;
+    let item_count = match count {
+        0 | 1 => String::new(),
+        count => count.to_string(),
+    };
+
+    let badge_style = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        right: StyleProp::Value(Units::Pixels(2.0 * scale)),
+        bottom: StyleProp::Value(Units::Pixels(2.0 * scale)),
+        width: StyleProp::Value(Units::Auto),
+        height: StyleProp::Value(Units::Auto),
+        padding: StyleProp::Value(Edge::axis(Units::Pixels(0.0), Units::Pixels(3.0 * scale))),
+        background_color: StyleProp::Value(if count > 1 {
+            Color::new(0.1, 0.1, 0.1, 0.8)
+        } else {
+            Color::TRANSPARENT
+        }),
+        ..default()
+    };
 
     let text_style = Style {
-        right: StyleProp::Value(Units::Pixels(5.0)),
+        width: StyleProp::Value(Units::Auto),
+        height: StyleProp::Value(Units::Auto),
+        ..default()
+    };
+
+    let selected_style = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        left: StyleProp::Value(Units::Pixels(0.0)),
+        top: StyleProp::Value(Units::Pixels(0.0)),
+        width: StyleProp::Value(Units::Stretch(1.0)),
+        height: StyleProp::Value(Units::Stretch(1.0)),
+        background_color: StyleProp::Value(if props.selected {
+            Color::new(1.0, 0.9, 0.4, 0.3)
+        } else {
+            Color::TRANSPARENT
+        }),
+        ..default()
+    };
+
+    //Zero width when nothing is being crafted
+    let progress_style = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        left: StyleProp::Value(Units::Pixels(0.0)),
+        bottom: StyleProp::Value(Units::Pixels(0.0)),
+        width: StyleProp::Value(Units::Percentage(props.progress.unwrap_or(0.0) * 100.0)),
+        height: StyleProp::Value(Units::Pixels(5.0 * scale)),
+        background_color: StyleProp::Value(Color::new(0.9, 0.8, 0.3, 1.0)),
+        ..default()
+    };
+
+    //Greys out the icon when the item can't be used
+    let disabled_style = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        left: StyleProp::Value(Units::Pixels(0.0)),
+        top: StyleProp::Value(Units::Pixels(0.0)),
+        width: StyleProp::Value(Units::Stretch(1.0)),
+        height: StyleProp::Value(Units::Stretch(1.0)),
+        background_color: StyleProp::Value(if props.disabled {
+            Color::new(0.2, 0.2, 0.2, 0.6)
+        } else {
+            Color::TRANSPARENT
+        }),
         ..default()
     };
 
     rsx! {
         <>
-            <Button on_event={Some(on_click_event)} styles={Some(button_style)} disabled={props.disabled}>
-                <Image handle={handle} styles={Some(image_style)} />
-                <Text content={item_count} styles={Some(text_style)} />
-            </Button>
+            <Element on_event={Some(on_hover_event)} styles={Some(slot_style)}>
+                <Button on_event={Some(on_click_event)} styles={Some(button_style)} disabled={props.disabled}>
+                    <Image handle={box_handle} styles={Some(box_style)} />
+                    <Background styles={Some(selected_style)} />
+                    <Image handle={handle} styles={Some(image_style)} />
+                    <Background styles={Some(badge_style)}>
+                        <Text content={item_count} size={14.0 * scale} styles={Some(text_style)} />
+                    </Background>
+                    <Background styles={Some(disabled_style)} />
+                    <Background styles={Some(progress_style)} />
+                </Button>
+            </Element>
         </>
     }
 }
@@ -83,14 +223,17 @@ pub fn InventoryUI(ui_props: UIProps) {
 
     context.bind(&ui_items);
 
-    let ii = ui_items.get().inventory_items;
+    //Every slot is drawn, empty or not, so items stay where they were put
+    let items = ui_items.get().inventory.items;
+    let selected = ui_items.get().selected;
     rsx! {
         <Element styles={ui_props.styles.clone()}>
-        {VecTracker::from(ii.iter().map(|item| {
+        {VecTracker::from(items.iter().enumerate().map(|(index, item)| {
             constructor! {
-                <Item event_type=
-                {UIEventType::InventoryEvent(*item)}
-                handle={Some(handles.get(&WorldObject::Item(item.item)).unwrap().clone())}/>
+                <Item event_type={UIEventType::InventoryEvent(index, *item)}
+                handle={Some(handles.get(&WorldObject::Item(item.item)).unwrap().clone())}
+                slot={Some(SlotRef::Inventory(index))}
+                selected={selected == Some(index)}/>
             }
         }))}
         </Element>
@@ -98,27 +241,55 @@ pub fn InventoryUI(ui_props: UIProps) {
 }
 
 #[widget]
-pub fn HandUI(ui_props: UIProps) {
+/// The slots of a container lying next to the player, nothing when there isn't one
+pub fn ContainerUI(ui_props: UIProps) {
     let ui_items =
         context.query_world::<Res<Binding<UIItems>>, _, _>(move |ui_items| ui_items.clone());
 
+    let handles = context.query_world::<Res<Graphics>, _, _>(|graphics| graphics.image_map.clone());
+
     context.bind(&ui_items);
 
-    let hand_item = ui_items.get().hand_item;
+    let items = ui_items.get().container.unwrap_or_default();
+    rsx! {
+        <Element styles={ui_props.styles.clone()}>
+        {VecTracker::from(items.iter().enumerate().map(|(index, item)| {
+            constructor! {
+                <Item event_type={UIEventType::None}
+                handle={Some(handles.get(&WorldObject::Item(item.item)).unwrap().clone())}
+                slot={Some(SlotRef::Container(index))}/>
+            }
+        }))}
+        </Element>
+    }
+}
+
+#[widget]
+/// The hand, body and head slots, clicking a filled one takes it off
+pub fn EquipmentUI(ui_props: UIProps) {
+    let ui_items =
+        context.query_world::<Res<Binding<UIItems>>, _, _>(move |ui_items| ui_items.clone());
+
+    context.bind(&ui_items);
+
+    let equipment = ui_items.get().equipment;
     let handles = context.query_world::<Res<Graphics>, _, _>(|graphics| graphics.image_map.clone());
 
-    if let Some(item) = hand_item {
-        rsx! {
-            <Element styles={ui_props.styles.clone()} >
-                <Item event_type={UIEventType::ToolEvent(hand_item.unwrap())}
-                handle={Some(handles.get(&WorldObject::Item(item.item)).unwrap().clone())}/>
-            </Element>
-        }
-    } else {
-        rsx! {
-            <Element styles={ui_props.styles.clone()} >
-            </Element>
-        }
+    rsx! {
+        <Element styles={ui_props.styles.clone()} >
+        {VecTracker::from(EquipSlot::ALL.iter().map(|slot| {
+            //Empty slots show the blank item so the others don't shuffle around
+            let equipped = equipment
+                .get(*slot)
+                .map(|item| ItemAndCount { item, count: 1 })
+                .unwrap_or_default();
+            constructor! {
+                <Item event_type={UIEventType::EquipmentEvent(*slot, equipped)}
+                handle={Some(handles.get(&WorldObject::Item(equipped.item)).unwrap().clone())}
+                slot={Some(SlotRef::Equipment(*slot))}/>
+            }
+        }))}
+        </Element>
     }
 }
 
@@ -129,16 +300,178 @@ pub fn RecipeUI(ui_props: UIProps) {
     context.bind(&ui_items);
 
     let handles = context.query_world::<Res<Graphics>, _, _>(|graphics| graphics.image_map.clone());
+    let crafting_book = context.query_world::<Res<CraftingBook>, _, _>(|book| book.clone());
     let crafting_items = ui_items.get().crafting_items;
+    let crafting_progress = ui_items.get().crafting_progress;
+    let inventory = ui_items.get().inventory;
+    let held = ui_items.get().equipment.hand;
 
     rsx! {
         <Element styles={ui_props.styles.clone()}>
         {VecTracker::from(crafting_items.iter().map(|item| {
+            let progress = crafting_progress
+                .filter(|(crafting, _)| crafting == item)
+                .map(|(_, progress)| progress);
+            let craftable = crafting_book
+                .recipes
+                .iter()
+                .find(|recipe| recipe.produces == *item)
+                .map(|recipe| inventory.can_craft(recipe, held))
+                .unwrap_or(false);
             constructor! {
                 <Item event_type={UIEventType::CraftEvent(*item)}
-                handle={println!("{:?}", item); Some(handles.get(item).unwrap().clone())}/>
+                handle={Some(handles.get(item).unwrap().clone())}
+                progress={progress}
+                disabled={!craftable}/>
             }
         }))}
         </ Element>
     }
 }
+
+/// Lists the ingredients of the hovered recipe along with how many the player has
+#[widget]
+pub fn RecipeTooltip() {
+    let hover = context.query_world::<Res<Binding<UIHover>>, _, _>(|hover| hover.clone());
+    let ui_items =
+        context.query_world::<Res<Binding<UIItems>>, _, _>(move |ui_items| ui_items.clone());
+    context.bind(&hover);
+    context.bind(&ui_items);
+    let scale = context.query_world::<Res<Binding<UIScale>>, _, _>(|scale| scale.clone());
+    context.bind(&scale);
+    let scale = scale.get().0;
+
+    let crafting_book = context.query_world::<Res<CraftingBook>, _, _>(|book| book.clone());
+    let handles = context.query_world::<Res<Graphics>, _, _>(|graphics| graphics.image_map.clone());
+    let inventory = ui_items.get().inventory;
+    let held = ui_items.get().equipment.hand;
+
+    let recipe = match hover.get().0 {
+        Some(UIEventType::CraftEvent(item)) => crafting_book
+            .recipes
+            .into_iter()
+            .find(|recipe| recipe.produces == item),
+        _ => None,
+    };
+
+    if let Some(recipe) = recipe {
+        let tooltip_style = Style {
+            padding: StyleProp::Value(Edge::all(Units::Pixels(8.0 * scale))),
+            row_between: StyleProp::Value(Units::Pixels(4.0 * scale)),
+            width: StyleProp::Value(Units::Pixels(160.0 * scale)),
+            height: StyleProp::Value(Units::Auto),
+            background_color: StyleProp::Value(Color::new(0.1, 0.1, 0.1, 0.8)),
+            ..default()
+        };
+        let row_style = Style {
+            layout_type: StyleProp::Value(LayoutType::Row),
+            col_between: StyleProp::Value(Units::Pixels(8.0 * scale)),
+            height: StyleProp::Value(Units::Pixels(32.0 * scale)),
+            ..default()
+        };
+        let icon_style = Style {
+            width: StyleProp::Value(Units::Pixels(32.0 * scale)),
+            height: StyleProp::Value(Units::Pixels(32.0 * scale)),
+            ..default()
+        };
+
+        let title = format!(
+            "{}x {} ({:.1}s)",
+            recipe.count,
+            recipe.produces.name(),
+            recipe.craft_time
+        );
+
+        // (icon, have/need text, text style) for every ingredient, catalysts are marked as kept
+        let rows: Vec<(u16, String, Style)> = recipe
+            .needed
+            .iter()
+            .map(|ingredient| (ingredient, ""))
+            .chain(
+                recipe
+                    .catalysts
+                    .iter()
+                    .map(|catalyst| (catalyst, " (kept)")),
+            )
+            .map(|(ingredient, suffix)| {
+                let mut have = inventory.count(ingredient.item);
+                if !suffix.is_empty() && held == Some(ingredient.item) {
+                    have += 1;
+                }
+                let handle = context.query_world::<ResMut<ImageManager>, _, _>(|mut manager| {
+                    manager.get(handles.get(&WorldObject::Item(ingredient.item)).unwrap())
+                });
+                let count_style = Style {
+                    color: StyleProp::Value(if have >= ingredient.count {
+                        Color::new(1.0, 1.0, 1.0, 1.0)
+                    } else {
+                        Color::new(1.0, 0.4, 0.4, 1.0)
+                    }),
+                    ..default()
+                };
+                let content = format!(
+                    "{}/{} {}{}",
+                    have,
+                    ingredient.count,
+                    ingredient.item.name(),
+                    suffix
+                );
+                (handle, content, count_style)
+            })
+            .collect();
+
+        let byproducts = recipe
+            .byproducts
+            .iter()
+            .map(|byproduct| byproduct.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let byproducts = if byproducts.is_empty() {
+            byproducts
+        } else {
+            format!("Also makes {}", byproducts)
+        };
+
+        rsx! {
+            <Background styles={Some(tooltip_style)}>
+                <Text content={title} size={18.0 * scale} />
+                <Text content={byproducts} size={14.0 * scale} />
+                {VecTracker::from(rows.iter().map(|(handle, content, count_style)| {
+                    constructor! {
+                        <Element styles={Some(row_style)}>
+                            <Image handle={*handle} styles={Some(icon_style)} />
+                            <Text content={content.clone()} size={16.0 * scale} styles={Some(count_style.clone())} />
+                        </Element>
+                    }
+                }))}
+            </Background>
+        }
+    } else {
+        rsx! {
+            <Element>
+            </Element>
+        }
+    }
+}
+
+/// Current day and season
+#[widget]
+pub fn ClockUI() {
+    let clock = context.query_world::<Res<Binding<UIClock>>, _, _>(|clock| clock.clone());
+    context.bind(&clock);
+    let scale = context.query_world::<Res<Binding<UIScale>>, _, _>(|scale| scale.clone());
+    context.bind(&scale);
+    let scale = scale.get().0;
+
+    let clock = clock.get();
+    let content = format!(
+        "Day {} - {:?}{}",
+        clock.day,
+        clock.season,
+        if clock.night { " (night)" } else { "" }
+    );
+
+    rsx! {
+        <Text content={content} size={20.0 * scale} />
+    }
+}
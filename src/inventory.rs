@@ -3,33 +3,130 @@ use bevy_inspector_egui::{Inspectable, RegisterInspectable};
 use kayak_ui::core::{Binding, MutableBound};
 
 use crate::{
-    crafting::CraftingBook, game_ui::UIItems, item::ItemAndCount, player::Hands, prelude::*,
+    crafting::{CraftingBook, CraftingQueue},
+    game_ui::UIItems,
+    item::ItemAndCount,
+    prelude::*,
     GameState,
 };
 
+/// Slots the player has without anything like a backpack adding more
 pub const INVENTORY_SIZE: usize = 7;
 /// Incomplete but should be used to limit number of a given item
 pub const INVENTORY_ITEM_SIZE: usize = 5;
+/// How close the player has to stand to a container to see inside it
+pub const CONTAINER_REACH: f32 = 1.5;
 
 pub struct InventoryPlugin;
 
-/// Main tracker for the player inventory
-#[derive(Component, Default, Inspectable, Clone)]
+/// Main tracker for the player inventory, empty slots hold ItemType::None
+#[derive(Component, Inspectable, Clone, Debug, PartialEq)]
 pub struct Inventory {
-    pub items: [ItemAndCount; INVENTORY_SIZE],
+    pub items: Vec<ItemAndCount>,
+}
+
+/// Items kept in something lying in the world, like a dropped backpack
+#[derive(Component, Default, Inspectable, Clone, Debug, PartialEq)]
+pub struct Container {
+    pub items: Vec<ItemAndCount>,
 }
 
 /// may be unused
 pub struct InventoryOverflow(pub usize);
 
+/// The inventory slot last picked with the hotbar or a click, highlighted in the UI
+#[derive(Default)]
+pub struct SelectedSlot(pub Option<usize>);
+
+/// The container next to the player, its slots are shown above the inventory
+#[derive(Default)]
+pub struct OpenContainer(pub Option<Entity>);
+
 impl Plugin for InventoryPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_update(GameState::Main).with_system(update_inventory_ui))
-            .register_inspectable::<Inventory>();
+        app.init_resource::<SelectedSlot>()
+            .init_resource::<OpenContainer>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Main)
+                    .with_system(select_slot)
+                    .with_system(open_nearby_container)
+                    .with_system(move_items)
+                    .with_system(
+                        update_inventory_ui
+                            .after(select_slot)
+                            .after(open_nearby_container)
+                            .after(move_items),
+                    ),
+            )
+            .register_inspectable::<Inventory>()
+            .register_inspectable::<Container>();
+    }
+}
+
+impl Container {
+    /// Every slot is empty, leaving only the item it came from
+    pub fn is_empty(&self) -> bool {
+        self.items.iter().all(|item| item.item == ItemType::None)
+    }
+}
+
+/// Stacks onto the same item as far as it can, otherwise the two slots trade places
+fn merge_or_swap(from: &mut ItemAndCount, to: &mut ItemAndCount) {
+    if from.item == to.item && from.item != ItemType::None {
+        let moved = from.count.min(INVENTORY_ITEM_SIZE.saturating_sub(to.count));
+        to.count += moved;
+        from.count -= moved;
+        if from.count == 0 {
+            *from = ItemAndCount::default();
+        }
+    } else {
+        std::mem::swap(from, to);
+    }
+}
+
+/// The stack in an inventory or container slot, equipment is handled separately
+fn stack_mut<'a>(
+    inventory: &'a mut Inventory,
+    container: Option<&'a mut Container>,
+    slot: SlotRef,
+) -> Option<&'a mut ItemAndCount> {
+    match slot {
+        SlotRef::Inventory(index) => inventory.items.get_mut(index),
+        SlotRef::Container(index) => container?.items.get_mut(index),
+        SlotRef::Equipment(_) => None,
+    }
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::with_capacity(INVENTORY_SIZE)
     }
 }
 
 impl Inventory {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            items: vec![ItemAndCount::default(); capacity],
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Grows or shrinks to a number of slots, returns whatever was in the slots that were removed
+    pub fn resize(&mut self, capacity: usize) -> Vec<ItemAndCount> {
+        if capacity >= self.items.len() {
+            self.items.resize(capacity, ItemAndCount::default());
+            return Vec::new();
+        }
+        self.items
+            .split_off(capacity)
+            .into_iter()
+            .filter(|item| item.item != ItemType::None)
+            .collect()
+    }
+
     pub fn add(&mut self, item_and_count: &ItemAndCount) -> Option<InventoryOverflow> {
         let mut remaining_amount = item_and_count.count;
 
@@ -39,8 +136,10 @@ impl Inventory {
             .filter(|item| item.item != ItemType::None)
         {
             if item.item == item_and_count.item {
-                let addable_item_count =
-                    std::cmp::min(remaining_amount, INVENTORY_ITEM_SIZE - item_and_count.count);
+                let addable_item_count = std::cmp::min(
+                    remaining_amount,
+                    INVENTORY_ITEM_SIZE.saturating_sub(item.count),
+                );
                 item.count += addable_item_count;
                 remaining_amount -= addable_item_count;
                 if remaining_amount == 0 {
@@ -55,9 +154,7 @@ impl Inventory {
             .filter(|item| item.item == ItemType::None)
         {
             item.item = item_and_count.item;
-            let addable_item_count =
-                std::cmp::min(remaining_amount, INVENTORY_ITEM_SIZE - item_and_count.count);
-            item.count = addable_item_count;
+            item.count = std::cmp::min(remaining_amount, INVENTORY_ITEM_SIZE);
             remaining_amount -= item.count;
             if remaining_amount == 0 {
                 return None;
@@ -90,12 +187,12 @@ impl Inventory {
         if existing {
             return Err(GameError::new(
                 GameErrorType::ItemMissing,
-                format!("Not enough items in inventory: {:?}", item_and_count.item),
+                format!("Not enough {} in inventory", item_and_count.item.name()),
             ));
         }
         Err(GameError::new(
             GameErrorType::ItemMissing,
-            format!("Item not in inventory: {:?}", item_and_count.item),
+            format!("No {} in inventory", item_and_count.item.name()),
         ))
     }
 
@@ -103,40 +200,184 @@ impl Inventory {
         let mut inventory_clone = self.clone();
         matches!(inventory_clone.remove(item_and_count), Ok(()))
     }
+
+    /// Total number of an item across every slot
+    pub fn count(&self, item: ItemType) -> usize {
+        self.items
+            .iter()
+            .filter(|slot| slot.item == item)
+            .map(|slot| slot.count)
+            .sum()
+    }
+}
+
+fn select_slot(
+    mut event_reader: EventReader<UIEvent>,
+    actions: Res<ActionState>,
+    mut selected: ResMut<SelectedSlot>,
+) {
+    let mut picked = None;
+    for ev in event_reader.iter() {
+        if let UIEventType::InventoryEvent(index, _) = ev.0 {
+            picked = Some(index);
+        }
+    }
+    for slot in actions.hotbar_just_pressed() {
+        picked = Some(slot);
+    }
+    if picked.is_some() && selected.0 != picked {
+        selected.0 = picked;
+    }
+}
+
+/// Opens the closest container in reach, there is only ever one open at a time
+fn open_nearby_container(
+    player: Query<&Transform, With<Player>>,
+    containers: Query<(Entity, &Transform), (With<Container>, Without<Player>)>,
+    mut open: ResMut<OpenContainer>,
+) {
+    let position = match player.get_single() {
+        Ok(transform) => transform.translation.truncate(),
+        Err(_) => return,
+    };
+    let nearest = containers
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation.truncate().distance(position)))
+        .filter(|(_, distance)| *distance <= CONTAINER_REACH)
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Greater))
+        .map(|(entity, _)| entity);
+    if open.0 != nearest {
+        open.0 = nearest;
+    }
+}
+
+/// Handles items dragged between the inventory, the equipment and the open container
+fn move_items(
+    mut commands: Commands,
+    mut event_reader: EventReader<UIEvent>,
+    graphics: Res<Graphics>,
+    mut messages: EventWriter<GameMessage>,
+    open: Res<OpenContainer>,
+    mut player: Query<(&Transform, &mut Inventory, &mut Equipment), With<Player>>,
+    mut containers: Query<&mut Container>,
+) {
+    let (transform, mut inventory, mut equipment) = player.single_mut();
+    let position = transform.translation.truncate();
+
+    for ev in event_reader.iter() {
+        let (from, to) = match ev.0 {
+            UIEventType::MoveEvent(from, to) => (from, to),
+            _ => continue,
+        };
+        match (from, to) {
+            (SlotRef::Equipment(slot), SlotRef::Inventory(_)) => {
+                if let Some(dropped) = equipment.unequip(&mut inventory, slot) {
+                    dropped.spawn(&mut commands, &graphics, &mut messages, position);
+                }
+            }
+            (SlotRef::Inventory(index), SlotRef::Equipment(slot)) => {
+                let item = match inventory.items.get(index) {
+                    Some(stack) => stack.item,
+                    None => continue,
+                };
+                if item.equip_slot() != Some(slot) {
+                    messages.send(GameMessage::warning(format!(
+                        "{} doesn't go in the {:?} slot",
+                        item.name(),
+                        slot
+                    )));
+                    continue;
+                }
+                match equipment.equip(&mut inventory, item) {
+                    Ok(Some(dropped)) => {
+                        dropped.spawn(&mut commands, &graphics, &mut messages, position)
+                    }
+                    Ok(None) => {}
+                    Err(e) => messages.send(e.into()),
+                }
+            }
+            (SlotRef::Equipment(_), _) | (_, SlotRef::Equipment(_)) => {
+                messages.send(GameMessage::warning(
+                    "Equipment can only be moved to and from the inventory",
+                ));
+            }
+            (from, to) => {
+                let mut container = open.0.and_then(|entity| containers.get_mut(entity).ok());
+                let stacks = (
+                    stack_mut(&mut inventory, container.as_deref_mut(), from).copied(),
+                    stack_mut(&mut inventory, container.as_deref_mut(), to).copied(),
+                );
+                let (mut from_stack, mut to_stack) = match stacks {
+                    (Some(from_stack), Some(to_stack)) => (from_stack, to_stack),
+                    _ => continue,
+                };
+                merge_or_swap(&mut from_stack, &mut to_stack);
+                if let Some(stack) = stack_mut(&mut inventory, container.as_deref_mut(), from) {
+                    *stack = from_stack;
+                }
+                if let Some(stack) = stack_mut(&mut inventory, container.as_deref_mut(), to) {
+                    *stack = to_stack;
+                }
+            }
+        }
+    }
 }
 
 /// Main system which tracks what should be rendered in UI
 /// FIXME this probably doesn't belong here
 fn update_inventory_ui(
     inventory_query: Query<
-        (&Inventory, &Hands),
-        (Or<(Changed<Inventory>, Changed<Hands>)>, With<Player>),
+        (
+            &Inventory,
+            &Equipment,
+            &CraftingQueue,
+            ChangeTrackers<Inventory>,
+            ChangeTrackers<Equipment>,
+            ChangeTrackers<CraftingQueue>,
+        ),
+        With<Player>,
     >,
     crafting_book: Res<CraftingBook>,
+    selected: Res<SelectedSlot>,
+    open: Res<OpenContainer>,
+    containers: Query<(&Container, ChangeTrackers<Container>)>,
     ui_items: Res<Binding<UIItems>>,
 ) {
-    if let Ok((inventory, hands)) = inventory_query.get_single() {
-        // get inventory items for ui
-        let inventory_items = inventory
-            .items
-            .into_iter()
-            .filter(|ic| ic.item != ItemType::None)
-            .collect();
+    if let Ok((
+        inventory,
+        equipment,
+        crafting_queue,
+        inventory_tracker,
+        equipment_tracker,
+        queue_tracker,
+    )) = inventory_query.get_single()
+    {
+        let container = open.0.and_then(|entity| containers.get(entity).ok());
+        let container_changed = container.map_or(false, |(_, tracker)| tracker.is_changed());
 
-        // get hand item for ui
-        let hand_item = hands.tool.map(|tool| ItemAndCount {
-            item: ItemType::Tool(tool),
-            count: 1,
-        });
+        //Recipes can be hot reloaded so they need to trigger a refresh too
+        if !inventory_tracker.is_changed()
+            && !equipment_tracker.is_changed()
+            && !queue_tracker.is_changed()
+            && !crafting_book.is_changed()
+            && !selected.is_changed()
+            && !open.is_changed()
+            && !container_changed
+        {
+            return;
+        }
 
         // get crafting items for ui
         let crafting_items = crafting_book.recipes.iter().map(|r| r.produces).collect();
 
         // update ui by updating binding object
         ui_items.set(UIItems {
-            inventory_items,
-            hand_item,
+            inventory: inventory.clone(),
+            selected: selected.0,
+            container: container.map(|(container, _)| container.items.clone()),
+            equipment: equipment.clone(),
             crafting_items,
+            crafting_progress: crafting_queue.progress(),
         });
     }
 }
@@ -1,7 +1,18 @@
-use crate::{prelude::Graphics, GameState};
+use crate::{
+    animation::{AnimatedSprite, Animator},
+    equipment::EquipSlot,
+    lighting::{Fire, LightSource, Occluder},
+    prelude::Graphics,
+    stats::StatModifiers,
+    terrain::TerrainType,
+    GameState,
+};
 use bevy::prelude::*;
 use bevy_inspector_egui::{Inspectable, RegisterInspectable};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+/// How strong a newly placed campfire burns
+const CAMPFIRE_STRENGTH: f32 = 7.0;
 
 #[derive(Component, Inspectable)]
 pub struct Pickupable {
@@ -16,7 +27,20 @@ pub struct Harvestable {
 }
 
 /// The core enum of the game, lists everything that can be held or placed in the game
-#[derive(Debug, Inspectable, PartialEq, Eq, Clone, Copy, Hash, Deserialize, Component)]
+#[derive(
+    Debug,
+    Inspectable,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    Hash,
+    Deserialize,
+    Serialize,
+    Component,
+)]
 pub enum WorldObject {
     Item(ItemType),
     Tree,
@@ -27,10 +51,13 @@ pub enum WorldObject {
     PluckedGrass,
     GrowingTree,
     CampFire,
+    GlowingMushroom,
 }
 
 /// Everything that can be in the players inventory
-#[derive(Inspectable, Debug, PartialEq, Eq, Clone, Copy, Hash, Deserialize)]
+#[derive(
+    Inspectable, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Deserialize, Serialize,
+)]
 pub enum ItemType {
     None,
     Tool(Tool),
@@ -38,26 +65,149 @@ pub enum ItemType {
     Twig,
     Grass,
     Wood,
+    /// A dug up tile of terrain which can be placed again
+    Turf(TerrainType),
+    Wearable(Wearable),
 }
 
-/// Everything the player can equip
-#[derive(Inspectable, Debug, PartialEq, Eq, Clone, Copy, Hash, Deserialize)]
+/// Everything the player can hold in their hand
+#[derive(
+    Inspectable, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Deserialize, Serialize,
+)]
 pub enum Tool {
     Axe,
     Shovel,
+    Torch,
+}
+
+/// Everything the player can wear on their body or head
+#[derive(
+    Inspectable, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Deserialize, Serialize,
+)]
+pub enum Wearable {
+    GrassSuit,
+    StrawHat,
+    /// Adds inventory slots while worn
+    Backpack,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 3] = [Tool::Axe, Tool::Shovel, Tool::Torch];
+}
+
+impl Wearable {
+    pub const ALL: [Wearable; 3] = [Wearable::GrassSuit, Wearable::StrawHat, Wearable::Backpack];
+
+    pub fn slot(self) -> EquipSlot {
+        match self {
+            Wearable::GrassSuit | Wearable::Backpack => EquipSlot::Body,
+            Wearable::StrawHat => EquipSlot::Head,
+        }
+    }
+
+    /// TODO it would be great to describe this outside of code, in a config or something
+    pub fn modifiers(self) -> StatModifiers {
+        match self {
+            Wearable::GrassSuit => StatModifiers {
+                insulation: 8.0,
+                speed: 0.95,
+            },
+            Wearable::StrawHat => StatModifiers {
+                insulation: 3.0,
+                ..Default::default()
+            },
+            Wearable::Backpack => StatModifiers {
+                speed: 0.97,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn extra_slots(self) -> usize {
+        match self {
+            Wearable::Backpack => 4,
+            _ => 0,
+        }
+    }
 }
 
 impl ItemType {
-    #[allow(dead_code)]
+    /// Every item, keep in sync with the enum so graphics can be checked for all of them
+    pub fn all() -> Vec<ItemType> {
+        let mut all = vec![
+            ItemType::None,
+            ItemType::Flint,
+            ItemType::Twig,
+            ItemType::Grass,
+            ItemType::Wood,
+        ];
+        all.extend(Tool::ALL.into_iter().map(ItemType::Tool));
+        all.extend(TerrainType::TURFS.into_iter().map(ItemType::Turf));
+        all.extend(Wearable::ALL.into_iter().map(ItemType::Wearable));
+        all
+    }
+
+    /// Where the item goes when equipped, None if it can't be
+    pub fn equip_slot(self) -> Option<EquipSlot> {
+        match self {
+            ItemType::Tool(_) => Some(EquipSlot::Hand),
+            ItemType::Wearable(wearable) => Some(wearable.slot()),
+            _ => None,
+        }
+    }
+
+    pub fn modifiers(self) -> StatModifiers {
+        match self {
+            ItemType::Wearable(wearable) => wearable.modifiers(),
+            _ => StatModifiers::default(),
+        }
+    }
+
+    /// Inventory slots added while the item is equipped
+    pub fn extra_slots(self) -> usize {
+        match self {
+            ItemType::Wearable(wearable) => wearable.extra_slots(),
+            _ => 0,
+        }
+    }
+
     pub fn name(self) -> String {
         match self {
             ItemType::Tool(tool) => format!("{:?}", tool),
+            ItemType::Turf(tile) => format!("{:?} Turf", tile),
+            ItemType::Wearable(wearable) => format!("{:?}", wearable),
             _ => format!("{:?}", self),
         }
     }
 }
 
 impl WorldObject {
+    /// Every object, keep in sync with the enum so graphics can be checked for all of them
+    pub fn all() -> Vec<WorldObject> {
+        let mut all: Vec<WorldObject> =
+            ItemType::all().into_iter().map(WorldObject::Item).collect();
+        all.extend([
+            WorldObject::Tree,
+            WorldObject::Stump,
+            WorldObject::Sapling,
+            WorldObject::DeadSapling,
+            WorldObject::Grass,
+            WorldObject::PluckedGrass,
+            WorldObject::GrowingTree,
+            WorldObject::CampFire,
+            WorldObject::GlowingMushroom,
+        ]);
+        all
+    }
+
+    /// Human readable name used by the UI
+    pub fn name(self) -> String {
+        match self {
+            WorldObject::Item(item) => item.name(),
+            _ => format!("{:?}", self),
+        }
+    }
+
     pub fn spawn(self, commands: &mut Commands, graphics: &Graphics, position: Vec2) -> Entity {
         let sprite = graphics
             .item_map
@@ -87,6 +237,32 @@ impl WorldObject {
             commands.entity(item).insert(pickup);
         }
 
+        if graphics
+            .animations
+            .contains_key(&AnimatedSprite::Object(self))
+        {
+            commands
+                .entity(item)
+                .insert(Animator::new(AnimatedSprite::Object(self)));
+        }
+
+        if let Some(light) = self.as_light() {
+            commands.entity(item).insert(light);
+        }
+
+        if let Some(occluder) = self.as_occluder() {
+            commands.entity(item).insert(occluder);
+        }
+
+        if self == WorldObject::CampFire {
+            commands
+                .entity(item)
+                .insert(Fire {
+                    strength: CAMPFIRE_STRENGTH,
+                })
+                .insert(Name::new("Fire"));
+        }
+
         if self.grows_into().is_some() {
             commands.entity(item).insert(GrowthTimer {
                 timer: Timer::from_seconds(3.0, false),
@@ -122,6 +298,18 @@ impl WorldObject {
         }
     }
 
+    pub fn is_plant(&self) -> bool {
+        matches!(
+            self,
+            WorldObject::Tree
+                | WorldObject::Sapling
+                | WorldObject::DeadSapling
+                | WorldObject::Grass
+                | WorldObject::PluckedGrass
+                | WorldObject::GrowingTree
+        )
+    }
+
     /// TODO it would be great to describe this outside of code, in a config or something
     pub fn as_harvest(&self) -> Option<Harvestable> {
         match self {
@@ -144,6 +332,25 @@ impl WorldObject {
         }
     }
 
+    pub fn as_light(&self) -> Option<LightSource> {
+        match self {
+            WorldObject::CampFire => Some(LightSource::campfire(CAMPFIRE_STRENGTH)),
+            WorldObject::GlowingMushroom => Some(LightSource::glowing_mushroom()),
+            _ => None,
+        }
+    }
+
+    /// Roughly the trunk or base of the object, what blocks light when shadows are on
+    pub fn as_occluder(&self) -> Option<Occluder> {
+        let half_size = match self {
+            WorldObject::Tree => Vec2::new(0.2, 0.15),
+            WorldObject::GrowingTree => Vec2::new(0.12, 0.1),
+            WorldObject::Stump => Vec2::new(0.18, 0.1),
+            _ => return None,
+        };
+        Some(Occluder { half_size })
+    }
+
     pub fn as_pickup(&self) -> Option<Pickupable> {
         if self.as_harvest().is_some() {
             return None;
@@ -172,8 +379,7 @@ pub struct ItemsPlugin;
 impl Plugin for ItemsPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(
-            SystemSet::on_enter(GameState::Main)
-                .with_system(Self::spawn_test_objects.after("graphics")),
+            SystemSet::on_enter(GameState::Main).with_system(Self::spawn_test_objects),
         )
         .add_system_set(
             SystemSet::on_update(GameState::Main)
@@ -201,12 +407,27 @@ impl ItemsPlugin {
     fn world_object_growth(
         mut commands: Commands,
         time: Res<Time>,
+        weather: Res<Weather>,
+        clock: Res<WorldClock>,
+        terrain: Option<Res<Terrain>>,
         graphics: Res<Graphics>,
         mut growable_query: Query<(Entity, &Transform, &WorldObject, Option<&mut GrowthTimer>)>,
     ) {
         for (ent, transform, world_object, regrowth_timer) in growable_query.iter_mut() {
             if let Some(mut timer) = regrowth_timer {
-                timer.timer.tick(time.delta());
+                //Rain makes everything grow faster, the season and ground decide what grows at all
+                let ground = terrain
+                    .as_ref()
+                    .and_then(|terrain| terrain.tile_at(transform.translation.truncate()))
+                    .map(|tile| tile.growth_multiplier(*world_object))
+                    .unwrap_or(1.0);
+                let multiplier = weather.kind.growth_multiplier()
+                    * clock.season().growth_multiplier(*world_object)
+                    * ground;
+                if multiplier <= 0.0 {
+                    continue;
+                }
+                timer.timer.tick(time.delta().mul_f32(multiplier));
                 if !timer.timer.finished() {
                     continue;
                 }
@@ -270,6 +491,16 @@ impl ItemsPlugin {
             &graphics,
             Vec2::new(1., 1.),
         ));
+        children.push(WorldObject::GlowingMushroom.spawn(
+            &mut commands,
+            &graphics,
+            Vec2::new(5., 2.),
+        ));
+        children.push(WorldObject::GlowingMushroom.spawn(
+            &mut commands,
+            &graphics,
+            Vec2::new(-5., -2.),
+        ));
         commands
             .spawn_bundle(TransformBundle::default())
             .insert(Name::new("Test Objects"))
@@ -289,6 +520,19 @@ pub struct ItemAndCount {
     pub count: usize,
 }
 
+impl ItemAndCount {
+    /// Drops the items as pickupables spread around a position, used when they don't fit
+    /// in the inventory
+    pub fn spawn_on_ground(self, commands: &mut Commands, graphics: &Graphics, position: Vec2) {
+        for i in 0..self.count {
+            //Golden angle spiral so the pile doesn't stack on a single spot
+            let angle = i as f32 * 2.4;
+            let offset = Vec2::new(angle.cos(), angle.sin()) * 0.3 * (i as f32).sqrt();
+            WorldObject::Item(self.item).spawn(commands, graphics, position + offset);
+        }
+    }
+}
+
 impl std::fmt::Display for ItemAndCount {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}x {:?}", self.count, self.item)
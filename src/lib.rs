@@ -0,0 +1,58 @@
+#![allow(clippy::redundant_field_names)]
+#![allow(clippy::expect_fun_call)]
+#![allow(clippy::type_complexity)]
+
+pub mod animation;
+pub mod assets;
+pub mod build;
+pub mod calendar;
+pub mod crafting;
+pub mod crafting_graph;
+pub mod equipment;
+pub mod error;
+pub mod game_camera;
+pub mod game_input;
+pub mod game_ui;
+pub mod game_ui_widgets;
+pub mod inventory;
+pub mod item;
+pub mod lighting;
+pub mod menus;
+pub mod messages;
+pub mod mouse;
+pub mod movement;
+pub mod player;
+pub mod prelude;
+pub mod settings;
+pub mod splash;
+pub mod sprite_packer;
+pub mod stats;
+pub mod terrain;
+pub mod weather;
+
+pub const HEIGHT: f32 = 900.;
+pub const RESOLUTION: f32 = 16.0 / 9.0;
+
+/// Everything random in the world is generated from this so a seed always plays out the same
+pub struct WorldSeed(pub u64);
+
+impl Default for WorldSeed {
+    fn default() -> Self {
+        WorldSeed(0x5eed)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+pub enum GameState {
+    Splash,
+    MainMenu,
+    Main,
+    /// Pushed on top of Main, which stops running but keeps being drawn
+    Paused,
+    /// Pushed on top of whichever menu opened it
+    Settings,
+    /// Pushed on top of Main when the player dies
+    GameOver,
+    /// Something failed to load, the reason is in LoadingProgress
+    LoadError,
+}
@@ -0,0 +1,793 @@
+use std::num::NonZeroU32;
+
+use bevy::ecs::system::lifetimeless::SRes;
+use bevy::reflect::TypeUuid;
+use bevy::render::camera::Camera2d;
+use bevy::render::render_asset::{self, RenderAsset, RenderAssets};
+use bevy::render::render_resource::std140::{AsStd140, Std140};
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::{render_resource::*, RenderApp, RenderStage};
+use bevy::sprite::{Material2dPipeline, Material2dPlugin, MaterialMesh2dBundle};
+use bevy::{prelude::*, sprite::SpecializedMaterial2d};
+use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+
+use crate::{prelude::*, GameState};
+
+pub struct LightingPlugin;
+
+/// Size of the uniform array in light_overlay.wgsl, used where storage buffers aren't supported
+pub const MAX_UNIFORM_LIGHTS: usize = 64;
+/// Hard cap when lights are in a storage buffer, the least relevant lights past this are dropped
+pub const MAX_STORAGE_LIGHTS: usize = 1024;
+/// The light count and view come first in the buffer, padded to the alignment of the light array
+const LIGHTS_HEADER_SIZE: usize = 32;
+/// Width and height in texels of the occlusion texture stretched over the camera view
+pub const OCCLUSION_RESOLUTION: u32 = 128;
+
+/// How many tiles of light each point of fire strength gives
+const FIRE_RADIUS_PER_STRENGTH: f32 = 0.35;
+
+/// Anything that lights up the night, drawn by the overlay shader
+#[derive(Clone, Component, Inspectable)]
+pub struct LightSource {
+    /// In tiles, the light fades out to nothing at this distance
+    pub radius: f32,
+    pub color: Color,
+    /// Brightness at the centre, 1 fully cancels the darkness
+    pub intensity: f32,
+    /// 0 is a steady light, 1 flickers wildly
+    pub flicker: f32,
+}
+
+/// A burning campfire, its light shrinks with its strength
+#[derive(Clone, Component, Inspectable)]
+pub struct Fire {
+    pub strength: f32,
+}
+
+/// Blocks light when shadows are on, a box around the base of the object
+#[derive(Clone, Component, Inspectable)]
+pub struct Occluder {
+    /// In tiles
+    pub half_size: Vec2,
+}
+
+/// Global lighting options
+pub struct LightingSettings {
+    /// Occluders cast shadows, costs a texture upload and some samples per light per pixel
+    pub shadows: bool,
+}
+
+/// Start of the light buffer in a GPU represntation
+#[derive(Clone, Default, AsStd140, Copy)]
+struct LightsHeaderGpu {
+    count: u32,
+    shadows: u32,
+    view_min: Vec2,
+    view_max: Vec2,
+    ambient: f32,
+}
+
+/// The buffer data for lights in a GPU represntation
+#[derive(Clone, Default, AsStd140, Copy)]
+struct LightGpu {
+    position: Vec2,
+    radius: f32,
+    intensity: f32,
+    color: Vec4,
+}
+
+/// Seconds of play so far, flicker follows this instead of real time so lights hold still
+/// while the game is paused
+#[derive(Default)]
+struct FlickerClock(f32);
+
+/// Every light touching the camera view this frame, most relevant first
+#[derive(Clone, Default)]
+struct ExtractedLights {
+    lights: Vec<LightGpu>,
+    view: LightView,
+    /// Daylight, 0 at night
+    ambient: f32,
+    /// Texels of the occlusion texture, empty when shadows are off
+    occlusion: Vec<u8>,
+}
+
+/// The part of the world the camera sees, lights outside of it are skipped
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LightView {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+/// The actual material responsible for rendering the darkness and lights
+#[derive(Clone, TypeUuid, Default)]
+#[uuid = "88d1793a-f685-4f16-aad8-42e9a07dd6b8"]
+struct LightMaterial;
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(Material2dPlugin::<LightMaterial>::default())
+            .init_resource::<LightingSettings>()
+            .init_resource::<FlickerClock>()
+            .add_startup_system(spawn_light_overlay)
+            .add_system_set(
+                SystemSet::on_update(GameState::Main)
+                    .with_system(tick_flicker_clock)
+                    .with_system(update_fire_lights)
+                    .with_system(update_held_lights),
+            )
+            .register_inspectable::<LightSource>()
+            .register_inspectable::<Fire>()
+            .register_inspectable::<Occluder>();
+        app.sub_app_mut(RenderApp)
+            .init_resource::<ExtractedLights>()
+            .add_system_to_stage(RenderStage::Extract, extract_lights)
+            .add_system_to_stage(RenderStage::Queue, prepare_lights);
+    }
+}
+
+impl Default for LightingSettings {
+    fn default() -> Self {
+        Self { shadows: true }
+    }
+}
+
+impl LightSource {
+    pub fn campfire(strength: f32) -> Self {
+        Self {
+            radius: strength * FIRE_RADIUS_PER_STRENGTH,
+            color: Color::rgb(1.0, 0.6, 0.3),
+            intensity: 1.0,
+            flicker: 0.3,
+        }
+    }
+
+    pub fn torch() -> Self {
+        Self {
+            radius: 1.6,
+            color: Color::rgb(1.0, 0.7, 0.4),
+            intensity: 0.9,
+            flicker: 0.4,
+        }
+    }
+
+    pub fn glowing_mushroom() -> Self {
+        Self {
+            radius: 0.8,
+            color: Color::rgb(0.3, 0.8, 1.0),
+            intensity: 0.6,
+            flicker: 0.0,
+        }
+    }
+
+    /// Intensity at a moment in time, phase offsets lights so they don't flicker in sync
+    pub fn flickered_intensity(&self, seconds: f32, phase: f32) -> f32 {
+        let noise = (seconds * 7.0 + phase).sin() * (seconds * 13.3 + phase * 1.7).sin();
+        self.intensity * (1.0 - self.flicker * 0.5 * (noise * 0.5 + 0.5))
+    }
+}
+
+impl LightView {
+    pub fn from_camera(transform: &GlobalTransform, projection: &OrthographicProjection) -> Self {
+        let centre = transform.translation.truncate();
+        let scale = transform.scale.truncate() * projection.scale;
+        Self {
+            min: centre + Vec2::new(projection.left, projection.bottom) * scale,
+            max: centre + Vec2::new(projection.right, projection.top) * scale,
+        }
+    }
+
+    /// If any of a light's circle is inside the view
+    pub fn touches(&self, position: Vec2, radius: f32) -> bool {
+        let closest = position.clamp(self.min, self.max);
+        closest.distance_squared(position) <= radius * radius
+    }
+
+    /// How much a light matters, big bright lights near the middle of the view come first
+    pub fn relevance(&self, position: Vec2, light: &LightSource) -> f32 {
+        let centre = (self.min + self.max) / 2.0;
+        light.intensity * light.radius / (1.0 + position.distance(centre))
+    }
+}
+
+/// Indices of the lights worth drawing, most relevant first and at most cap of them.
+/// Pure so it can be checked without a GPU
+pub fn cull_lights(view: &LightView, lights: &[(Vec2, &LightSource)], cap: usize) -> Vec<usize> {
+    let mut visible: Vec<(usize, f32)> = lights
+        .iter()
+        .enumerate()
+        .filter(|(_, (position, light))| {
+            light.radius > 0.0 && light.intensity > 0.0 && view.touches(*position, light.radius)
+        })
+        .map(|(index, (position, light))| (index, view.relevance(*position, light)))
+        .collect();
+    //Ties keep their order so lights don't swap places between frames
+    visible.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+    });
+    visible.truncate(cap);
+    visible.into_iter().map(|(index, _)| index).collect()
+}
+
+/// Marks every texel covered by an occluder, rows go from the top of the view down.
+/// Pure so it can be checked without a GPU
+pub fn rasterize_occluders(
+    view: &LightView,
+    occluders: &[(Vec2, &Occluder)],
+    resolution: u32,
+) -> Vec<u8> {
+    let mut texels = vec![0; (resolution * resolution) as usize];
+    let size = view.max - view.min;
+    if size.x <= 0.0 || size.y <= 0.0 {
+        return texels;
+    }
+    let to_texel = |position: Vec2| (position - view.min) / size * resolution as f32;
+    let limit = Vec2::splat(resolution as f32);
+
+    for (position, occluder) in occluders.iter() {
+        let min = to_texel(*position - occluder.half_size)
+            .floor()
+            .clamp(Vec2::ZERO, limit);
+        let max = to_texel(*position + occluder.half_size)
+            .ceil()
+            .clamp(Vec2::ZERO, limit);
+        for y in min.y as u32..max.y as u32 {
+            let row = resolution - 1 - y;
+            for x in min.x as u32..max.x as u32 {
+                texels[(row * resolution + x) as usize] = 255;
+            }
+        }
+    }
+    texels
+}
+
+/// Copies the lights the camera can see out of the game world each frame
+fn extract_lights(
+    mut commands: Commands,
+    flicker_clock: Res<FlickerClock>,
+    settings: Res<LightingSettings>,
+    clock: Option<Res<WorldClock>>,
+    camera: Query<(&GlobalTransform, &OrthographicProjection), With<Camera2d>>,
+    lights: Query<(Entity, &GlobalTransform, &LightSource)>,
+    occluders: Query<(&GlobalTransform, &Occluder)>,
+) {
+    let view = match camera.get_single() {
+        Ok((transform, projection)) => LightView::from_camera(transform, projection),
+        Err(_) => {
+            commands.insert_resource(ExtractedLights::default());
+            return;
+        }
+    };
+
+    let seconds = flicker_clock.0;
+    let all_lights: Vec<(Entity, Vec2, &LightSource)> = lights
+        .iter()
+        .map(|(entity, transform, light)| (entity, transform.translation.truncate(), light))
+        .collect();
+    let candidates: Vec<(Vec2, &LightSource)> = all_lights
+        .iter()
+        .map(|(_, position, light)| (*position, *light))
+        .collect();
+
+    let lights = cull_lights(&view, &candidates, MAX_STORAGE_LIGHTS)
+        .into_iter()
+        .map(|index| {
+            let (entity, position, light) = all_lights[index];
+            LightGpu {
+                position,
+                radius: light.radius,
+                intensity: light.flickered_intensity(seconds, entity.id() as f32),
+                color: Vec4::from(light.color.as_rgba_f32()),
+            }
+        })
+        .collect();
+
+    let occlusion = if settings.shadows {
+        let occluders: Vec<(Vec2, &Occluder)> = occluders
+            .iter()
+            .map(|(transform, occluder)| (transform.translation.truncate(), occluder))
+            .collect();
+        rasterize_occluders(&view, &occluders, OCCLUSION_RESOLUTION)
+    } else {
+        Vec::new()
+    };
+
+    commands.insert_resource(ExtractedLights {
+        lights,
+        view,
+        ambient: clock.map(|clock| clock.daylight()).unwrap_or(0.0),
+        occlusion,
+    });
+}
+
+/// Storage buffers aren't available everywhere (WebGL2), the uniform array is the fallback
+fn supports_storage_lights(render_device: &RenderDevice) -> bool {
+    render_device.limits().max_storage_buffers_per_shader_stage > 0
+}
+
+/// Header followed by the lights, uniform buffers are always padded to full size
+fn light_buffer_contents(extracted: &ExtractedLights, storage: bool) -> Vec<u8> {
+    let lights = &extracted.lights;
+    let cap = if storage {
+        MAX_STORAGE_LIGHTS
+    } else {
+        MAX_UNIFORM_LIGHTS
+    };
+    let lights = &lights[..lights.len().min(cap)];
+
+    let header = LightsHeaderGpu {
+        count: lights.len() as u32,
+        shadows: !extracted.occlusion.is_empty() as u32,
+        view_min: extracted.view.min,
+        view_max: extracted.view.max,
+        ambient: extracted.ambient,
+    };
+    let mut contents = header.as_std140().as_bytes().to_vec();
+    contents.resize(LIGHTS_HEADER_SIZE, 0);
+    for light in lights.iter() {
+        contents.extend_from_slice(light.as_std140().as_bytes());
+    }
+    let light_size = LightGpu::default().as_std140().as_bytes().len();
+    let padded_count = if storage {
+        lights.len().max(1)
+    } else {
+        MAX_UNIFORM_LIGHTS
+    };
+    contents.resize(LIGHTS_HEADER_SIZE + padded_count * light_size, 0);
+    contents
+}
+
+fn create_light_buffer(render_device: &RenderDevice, contents: &[u8], storage: bool) -> Buffer {
+    let usage = if storage {
+        BufferUsages::STORAGE
+    } else {
+        BufferUsages::UNIFORM
+    };
+    render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("lights"),
+        usage: usage | BufferUsages::COPY_DST,
+        contents,
+    })
+}
+
+fn occlusion_size() -> Extent3d {
+    Extent3d {
+        width: OCCLUSION_RESOLUTION,
+        height: OCCLUSION_RESOLUTION,
+        depth_or_array_layers: 1,
+    }
+}
+
+fn create_light_bind_group(
+    render_device: &RenderDevice,
+    pipeline: &Material2dPipeline<LightMaterial>,
+    buffer: &Buffer,
+    occlusion_view: &TextureView,
+    occlusion_sampler: &Sampler,
+) -> BindGroup {
+    render_device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.material2d_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(occlusion_view),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::Sampler(occlusion_sampler),
+            },
+        ],
+    })
+}
+
+fn prepare_lights(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    pipeline: Res<Material2dPipeline<LightMaterial>>,
+    extracted_lights: Res<ExtractedLights>,
+    mut assets: ResMut<RenderAssets<LightMaterial>>,
+) {
+    for asset in assets.values_mut() {
+        let contents = light_buffer_contents(&extracted_lights, asset.storage);
+        if contents.len() > asset.capacity {
+            //Storage buffers grow with the number of lights
+            asset.buffer = create_light_buffer(&render_device, &contents, asset.storage);
+            asset.capacity = contents.len();
+            asset.bind_group = create_light_bind_group(
+                &render_device,
+                &pipeline,
+                &asset.buffer,
+                &asset.occlusion_view,
+                &asset.occlusion_sampler,
+            );
+        } else {
+            render_queue.write_buffer(&asset.buffer, 0, &contents);
+        }
+
+        if !extracted_lights.occlusion.is_empty() {
+            render_queue.write_texture(
+                ImageCopyTexture {
+                    texture: &asset.occlusion,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                &extracted_lights.occlusion,
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(OCCLUSION_RESOLUTION),
+                    rows_per_image: None,
+                },
+                occlusion_size(),
+            );
+        }
+    }
+}
+
+fn tick_flicker_clock(time: Res<Time>, mut flicker_clock: ResMut<FlickerClock>) {
+    flicker_clock.0 += time.delta_seconds();
+}
+
+fn update_fire_lights(mut fires: Query<(&Fire, &mut LightSource), Changed<Fire>>) {
+    for (fire, mut light) in fires.iter_mut() {
+        light.radius = fire.strength * FIRE_RADIUS_PER_STRENGTH;
+    }
+}
+
+/// Holding a torch lights up whoever is holding it
+fn update_held_lights(
+    mut commands: Commands,
+    holders: Query<(Entity, &Equipment, Option<&LightSource>), Changed<Equipment>>,
+) {
+    for (entity, equipment, light) in holders.iter() {
+        match (equipment.tool(), light) {
+            (Some(Tool::Torch), None) => {
+                commands.entity(entity).insert(LightSource::torch());
+            }
+            (Some(Tool::Torch), Some(_)) => {}
+            (_, Some(_)) => {
+                commands.entity(entity).remove::<LightSource>();
+            }
+            (_, None) => {}
+        }
+    }
+}
+
+// Creates a single instance of the light overlay material and make it follow the camera
+fn spawn_light_overlay(
+    mut commands: Commands,
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    mut material_assets: ResMut<Assets<LightMaterial>>,
+) {
+    commands
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: mesh_assets
+                .add(Mesh::from(shape::Quad::new(Vec2::splat(TILE_SIZE * 100.0))))
+                .into(),
+            material: material_assets.add(LightMaterial),
+            ..default()
+        })
+        .insert(CameraFollower { offset: -10.0 })
+        .insert(Name::new("Overlay"));
+}
+
+struct LightMaterialGpu {
+    bind_group: BindGroup,
+    buffer: Buffer,
+    storage: bool,
+    /// Size of the buffer in bytes
+    capacity: usize,
+    occlusion: Texture,
+    occlusion_view: TextureView,
+    occlusion_sampler: Sampler,
+}
+
+impl SpecializedMaterial2d for LightMaterial {
+    /// If the lights are in a storage buffer
+    type Key = bool;
+
+    fn key(material: &<Self as RenderAsset>::PreparedAsset) -> Self::Key {
+        material.storage
+    }
+
+    fn specialize(storage: Self::Key, descriptor: &mut RenderPipelineDescriptor) {
+        if storage {
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("STORAGE_LIGHTS".to_string());
+            }
+        }
+    }
+
+    fn bind_group(
+        material: &<Self as bevy::render::render_asset::RenderAsset>::PreparedAsset,
+    ) -> &bevy::render::render_resource::BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(
+        render_device: &bevy::render::renderer::RenderDevice,
+    ) -> bevy::render::render_resource::BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: if supports_storage_lights(render_device) {
+                            BufferBindingType::Storage { read_only: true }
+                        } else {
+                            BufferBindingType::Uniform
+                        },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    fn fragment_shader(asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(asset_server.load("light_overlay.wgsl"))
+    }
+}
+
+impl RenderAsset for LightMaterial {
+    type ExtractedAsset = LightMaterial;
+    type PreparedAsset = LightMaterialGpu;
+    type Param = (SRes<RenderDevice>, SRes<Material2dPipeline<LightMaterial>>);
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        _extracted_asset: Self::ExtractedAsset,
+        (render_device, pipeline): &mut bevy::ecs::system::SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, render_asset::PrepareAssetError<Self::ExtractedAsset>> {
+        //Filled in every frame by prepare_lights
+        let storage = supports_storage_lights(render_device);
+        let contents = light_buffer_contents(&ExtractedLights::default(), storage);
+        let buffer = create_light_buffer(render_device, &contents, storage);
+
+        let occlusion = render_device.create_texture(&TextureDescriptor {
+            label: Some("light_occlusion"),
+            size: occlusion_size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        let occlusion_view = occlusion.create_view(&TextureViewDescriptor::default());
+        let occlusion_sampler = render_device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = create_light_bind_group(
+            render_device,
+            pipeline,
+            &buffer,
+            &occlusion_view,
+            &occlusion_sampler,
+        );
+
+        Ok(LightMaterialGpu {
+            bind_group,
+            buffer,
+            storage,
+            capacity: contents.len(),
+            occlusion,
+            occlusion_view,
+            occlusion_sampler,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn light(radius: f32, intensity: f32) -> LightSource {
+        LightSource {
+            radius,
+            color: Color::WHITE,
+            intensity,
+            flicker: 0.0,
+        }
+    }
+
+    fn view() -> LightView {
+        LightView {
+            min: Vec2::ZERO,
+            max: Vec2::splat(16.0),
+        }
+    }
+
+    #[test]
+    fn lights_outside_the_view_are_culled() {
+        let lamp = light(2.0, 1.0);
+        let lights = [
+            (Vec2::new(8.0, 8.0), &lamp),
+            //Centre is outside but the circle reaches in
+            (Vec2::new(17.0, 8.0), &lamp),
+            (Vec2::new(20.0, 8.0), &lamp),
+            (Vec2::new(-5.0, -5.0), &lamp),
+        ];
+        assert_eq!(cull_lights(&view(), &lights, 10), vec![0, 1]);
+    }
+
+    #[test]
+    fn dark_and_empty_lights_are_culled() {
+        let off = light(2.0, 0.0);
+        let tiny = light(0.0, 1.0);
+        let lights = [(Vec2::splat(8.0), &off), (Vec2::splat(8.0), &tiny)];
+        assert!(cull_lights(&view(), &lights, 10).is_empty());
+    }
+
+    #[test]
+    fn closer_and_brighter_lights_come_first() {
+        //A bright light a tile from the centre still beats a dim one right on it
+        let dim = light(2.0, 0.4);
+        let bright = light(2.0, 1.0);
+        let lights = [
+            (Vec2::new(2.0, 2.0), &bright),
+            (Vec2::new(8.0, 8.0), &dim),
+            (Vec2::new(8.0, 9.0), &bright),
+        ];
+        assert_eq!(cull_lights(&view(), &lights, 10), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn ties_keep_their_order() {
+        let lamp = light(2.0, 1.0);
+        //Same distance from the centre in different directions
+        let lights = [
+            (Vec2::new(10.0, 8.0), &lamp),
+            (Vec2::new(6.0, 8.0), &lamp),
+            (Vec2::new(8.0, 10.0), &lamp),
+            (Vec2::new(8.0, 6.0), &lamp),
+        ];
+        assert_eq!(cull_lights(&view(), &lights, 10), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn only_the_most_relevant_lights_fit_under_the_cap() {
+        let lamp = light(2.0, 1.0);
+        let lights: Vec<(Vec2, &LightSource)> = (0..10)
+            .map(|i| (Vec2::new(8.0 + i as f32, 8.0), &lamp))
+            .collect();
+        assert_eq!(cull_lights(&view(), &lights, 3), vec![0, 1, 2]);
+        assert!(cull_lights(&view(), &lights, 0).is_empty());
+    }
+
+    #[test]
+    fn uniform_buffer_falls_back_to_its_own_cap() {
+        let extracted = ExtractedLights {
+            lights: vec![LightGpu::default(); MAX_UNIFORM_LIGHTS + 10],
+            ..Default::default()
+        };
+        let light_size = LightGpu::default().as_std140().as_bytes().len();
+
+        let uniform = light_buffer_contents(&extracted, false);
+        let count = u32::from_le_bytes(uniform[0..4].try_into().unwrap());
+        assert_eq!(count as usize, MAX_UNIFORM_LIGHTS);
+        assert_eq!(
+            uniform.len(),
+            LIGHTS_HEADER_SIZE + MAX_UNIFORM_LIGHTS * light_size
+        );
+
+        let storage = light_buffer_contents(&extracted, true);
+        let count = u32::from_le_bytes(storage[0..4].try_into().unwrap());
+        assert_eq!(count as usize, MAX_UNIFORM_LIGHTS + 10);
+    }
+
+    #[test]
+    fn uniform_buffer_is_padded_when_there_are_few_lights() {
+        let extracted = ExtractedLights {
+            lights: vec![LightGpu::default(); 2],
+            ..Default::default()
+        };
+        let light_size = LightGpu::default().as_std140().as_bytes().len();
+        assert_eq!(
+            light_buffer_contents(&extracted, false).len(),
+            LIGHTS_HEADER_SIZE + MAX_UNIFORM_LIGHTS * light_size
+        );
+        assert_eq!(
+            light_buffer_contents(&extracted, true).len(),
+            LIGHTS_HEADER_SIZE + 2 * light_size
+        );
+    }
+
+    fn texel(texels: &[u8], x: u32, row: u32) -> u8 {
+        texels[(row * OCCLUSION_RESOLUTION + x) as usize]
+    }
+
+    #[test]
+    fn occluders_fill_the_grid_corners() {
+        //16 tiles over 128 texels puts every tile at 8 texels
+        let block = Occluder {
+            half_size: Vec2::splat(0.5),
+        };
+        let occluders = [
+            (Vec2::new(0.5, 0.5), &block),
+            (Vec2::new(15.5, 15.5), &block),
+        ];
+        let texels = rasterize_occluders(&view(), &occluders, OCCLUSION_RESOLUTION);
+        assert_eq!(texels.len(), 128 * 128);
+
+        //Bottom left of the view is the last row
+        assert_eq!(texel(&texels, 0, 127), 255);
+        assert_eq!(texel(&texels, 7, 120), 255);
+        assert_eq!(texel(&texels, 8, 127), 0);
+        assert_eq!(texel(&texels, 0, 119), 0);
+
+        //Top right is the first row
+        assert_eq!(texel(&texels, 127, 0), 255);
+        assert_eq!(texel(&texels, 120, 7), 255);
+        assert_eq!(texel(&texels, 119, 0), 0);
+        assert_eq!(texel(&texels, 127, 8), 0);
+
+        let filled = texels.iter().filter(|texel| **texel == 255).count();
+        assert_eq!(filled, 2 * 8 * 8);
+    }
+
+    #[test]
+    fn occluders_past_the_edge_are_clipped() {
+        let wide = Occluder {
+            half_size: Vec2::new(1.0, 0.5),
+        };
+        let occluders = [
+            //Half hangs off the right edge
+            (Vec2::new(16.0, 8.5), &wide),
+            (Vec2::new(40.0, 40.0), &wide),
+            (Vec2::new(-3.0, 8.5), &wide),
+        ];
+        let texels = rasterize_occluders(&view(), &occluders, OCCLUSION_RESOLUTION);
+        let filled = texels.iter().filter(|texel| **texel == 255).count();
+        assert_eq!(filled, 8 * 8);
+        assert_eq!(texel(&texels, 127, 127 - 64), 255);
+        assert_eq!(texel(&texels, 120, 127 - 71), 255);
+        assert_eq!(texel(&texels, 119, 127 - 64), 0);
+    }
+
+    #[test]
+    fn empty_view_has_no_occlusion() {
+        let block = Occluder {
+            half_size: Vec2::splat(0.5),
+        };
+        let empty = LightView {
+            min: Vec2::ZERO,
+            max: Vec2::ZERO,
+        };
+        let texels = rasterize_occluders(&empty, &[(Vec2::ZERO, &block)], 8);
+        assert!(texels.iter().all(|texel| *texel == 0));
+    }
+}
@@ -1,53 +1,30 @@
-#![allow(clippy::redundant_field_names)]
-#![allow(clippy::expect_fun_call)]
-#![allow(clippy::type_complexity)]
-use bevy::{prelude::*, window::PresentMode};
+use bevy::{asset::AssetServerSettings, prelude::*};
 use bevy_asset_loader::*;
 use bevy_inspector_egui::{WorldInspectorParams, WorldInspectorPlugin};
 
-mod assets;
-mod build;
-mod crafting;
-mod error;
-mod fire_graphics;
-mod game_camera;
-mod game_ui;
-mod game_ui_widgets;
-mod inventory;
-mod item;
-mod mouse;
-mod player;
-mod prelude;
-
-use build::BuildingPlugin;
-use mouse::MousePlugin;
+use bevy_survival_crafting_game::build::BuildingPlugin;
+use bevy_survival_crafting_game::menus::MenuPlugin;
+use bevy_survival_crafting_game::mouse::MousePlugin;
 // todo implement `PluginGroup`
-use prelude::{
-    CraftingPlugin, FireGraphicsPlugin, GameAssetsPlugin, GameCameraPlugin, GameUIPlugin,
-    InventoryPlugin, ItemsPlugin, PlayerPlugin,
+use bevy_survival_crafting_game::assets::{DataAssets, FontAssets, ImageAssets};
+use bevy_survival_crafting_game::prelude::{
+    AnimationPlugin, CalendarPlugin, CraftingPlugin, EquipmentPlugin, GameAssetsPlugin,
+    GameCameraPlugin, GameInputPlugin, GameUIPlugin, InventoryPlugin, ItemsPlugin, LightingPlugin,
+    MessagePlugin, MovementPlugin, PlayerPlugin, StatsPlugin, TerrainPlugin, WeatherPlugin,
 };
-
-pub const HEIGHT: f32 = 900.;
-pub const RESOLUTION: f32 = 16.0 / 9.0;
-
-#[derive(Clone, Eq, PartialEq, Debug, Hash)]
-pub enum GameState {
-    Splash,
-    Main,
-}
-
-//TODO move to assets.rs
-#[derive(AssetCollection)]
-struct ImageAssets {
-    #[asset(path = "bevy_survival_sprites.png")]
-    pub sprite_sheet: Handle<Image>,
-}
+use bevy_survival_crafting_game::settings::{Settings, SettingsPlugin, SETTINGS_PATH};
+use bevy_survival_crafting_game::splash::SplashPlugin;
+use bevy_survival_crafting_game::{GameState, HEIGHT, RESOLUTION};
 
 fn main() {
     let mut app = App::new();
+    //Needed before the window exists so vsync is right from the first frame
+    let settings = Settings::load_or_default(SETTINGS_PATH);
+    //GameAssetsPlugin moves on to the game once the graphics are built from these
     AssetLoader::new(GameState::Splash)
-        .continue_to_state(GameState::Main)
         .with_collection::<ImageAssets>()
+        .with_collection::<DataAssets>()
+        .with_collection::<FontAssets>()
         .build(&mut app);
     app.add_state(GameState::Splash)
         .insert_resource(ClearColor(Color::hex("b0c060").unwrap()))
@@ -55,8 +32,14 @@ fn main() {
             width: HEIGHT * RESOLUTION,
             height: HEIGHT,
             title: "DST clone".to_string(),
-            present_mode: PresentMode::Fifo,
-            resizable: false,
+            present_mode: settings.present_mode(),
+            resizable: true,
+            ..Default::default()
+        })
+        .insert_resource(settings)
+        //Recipes and shaders are hot reloaded while the game runs
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: true,
             ..Default::default()
         })
         .add_plugins(DefaultPlugins)
@@ -65,15 +48,27 @@ fn main() {
             ..Default::default()
         })
         .add_plugin(WorldInspectorPlugin::new())
+        .add_plugin(GameInputPlugin)
         .add_plugin(GameCameraPlugin)
         .add_plugin(GameAssetsPlugin)
+        .add_plugin(SplashPlugin)
         .add_plugin(PlayerPlugin)
+        .add_plugin(TerrainPlugin)
+        .add_plugin(MovementPlugin)
+        .add_plugin(AnimationPlugin)
         .add_plugin(MousePlugin)
         .add_plugin(ItemsPlugin)
         .add_plugin(InventoryPlugin)
+        .add_plugin(EquipmentPlugin)
         .add_plugin(BuildingPlugin)
         .add_plugin(CraftingPlugin)
-        .add_plugin(FireGraphicsPlugin)
+        .add_plugin(LightingPlugin)
+        .add_plugin(CalendarPlugin)
+        .add_plugin(StatsPlugin)
+        .add_plugin(WeatherPlugin)
         .add_plugin(GameUIPlugin)
+        .add_plugin(MessagePlugin)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(MenuPlugin)
         .run();
 }
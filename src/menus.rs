@@ -0,0 +1,422 @@
+use bevy::app::AppExit;
+use bevy::prelude::{
+    warn, App, EventReader, EventWriter, Input, KeyCode, ParallelSystemDescriptorCoercion, Plugin,
+    Query, Res, ResMut, State, SystemSet, With,
+};
+use kayak_ui::{
+    core::{
+        constructor, rsx,
+        styles::{Edge, LayoutType, PositionType, Style, StyleProp, Units},
+        widget, Binding, Bound, Color, EventType, MutableBound, OnEvent, VecTracker, WidgetProps,
+    },
+    widgets::{Background, Button, Element, Text},
+};
+
+use crate::{
+    game_input::{InputBinding, InputBindings, BINDINGS_PATH},
+    game_ui::{UIScale, UISettings},
+    prelude::*,
+    settings::Settings,
+    GameState,
+};
+
+pub struct MenuPlugin;
+
+/// Sent by the menu buttons, handled by MenuPlugin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuEvent {
+    None,
+    Play,
+    Resume,
+    OpenSettings,
+    CloseSettings,
+    Respawn,
+    Quit,
+    ChangeVolume(f32),
+    ChangeUIScale(f32),
+    ToggleVsync,
+    /// Waits for the next key press and binds it to the action
+    Rebind(InputAction),
+}
+
+impl Default for MenuEvent {
+    fn default() -> Self {
+        MenuEvent::None
+    }
+}
+
+/// The action waiting for a key to be pressed on the settings screen
+#[derive(Default)]
+pub struct Rebinding(pub Option<InputAction>);
+
+/// Everything the menus show, bound so they redraw when any of it changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct UIMenu {
+    pub screen: GameState,
+    /// Main is running or paused underneath, so the HUD should be drawn
+    pub in_game: bool,
+    pub settings: Settings,
+    /// Every action with a readable list of what triggers it
+    pub bindings: Vec<(InputAction, String)>,
+    pub rebinding: Option<InputAction>,
+}
+
+impl Default for UIMenu {
+    fn default() -> Self {
+        Self {
+            screen: GameState::Splash,
+            in_game: false,
+            settings: Settings::default(),
+            bindings: Vec::new(),
+            rebinding: None,
+        }
+    }
+}
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rebinding>()
+            .add_event::<MenuEvent>()
+            .add_system(Self::handle_menu_events)
+            .add_system(Self::update_menu_ui.after(Self::handle_menu_events))
+            .add_system_set(
+                SystemSet::on_update(GameState::Main)
+                    .with_system(Self::pause_game)
+                    .with_system(Self::check_game_over),
+            )
+            .add_system_set(SystemSet::on_update(GameState::Paused).with_system(Self::resume_game))
+            .add_system_set(
+                SystemSet::on_update(GameState::Settings)
+                    .with_system(Self::close_settings)
+                    .with_system(Self::capture_rebind.after(Self::close_settings)),
+            );
+    }
+}
+
+impl MenuPlugin {
+    fn pause_game(actions: Res<ActionState>, mut state: ResMut<State<GameState>>) {
+        if actions.just_pressed(InputAction::Pause) {
+            if let Err(e) = state.push(GameState::Paused) {
+                warn!("{}", e);
+            }
+        }
+    }
+
+    fn resume_game(actions: Res<ActionState>, mut state: ResMut<State<GameState>>) {
+        if actions.just_pressed(InputAction::Pause) || actions.just_pressed(InputAction::Cancel) {
+            if let Err(e) = state.pop() {
+                warn!("{}", e);
+            }
+        }
+    }
+
+    /// Cancel leaves the settings, unless it is the key being bound
+    fn close_settings(
+        actions: Res<ActionState>,
+        rebinding: Res<Rebinding>,
+        mut events: EventWriter<MenuEvent>,
+    ) {
+        if rebinding.0.is_none() && actions.just_pressed(InputAction::Cancel) {
+            events.send(MenuEvent::CloseSettings);
+        }
+    }
+
+    fn check_game_over(player: Query<&Health, With<Player>>, mut state: ResMut<State<GameState>>) {
+        if player.iter().any(|health| health.is_dead()) {
+            if let Err(e) = state.push(GameState::GameOver) {
+                warn!("{}", e);
+            }
+        }
+    }
+
+    /// Binds the next key pressed to the waiting action, escape gives up
+    fn capture_rebind(
+        keyboard: Res<Input<KeyCode>>,
+        mut rebinding: ResMut<Rebinding>,
+        mut bindings: ResMut<InputBindings>,
+    ) {
+        let action = match rebinding.0 {
+            Some(action) => action,
+            None => return,
+        };
+        let key = match keyboard.get_just_pressed().next() {
+            Some(key) => *key,
+            None => return,
+        };
+        rebinding.0 = None;
+        if key == KeyCode::Escape {
+            return;
+        }
+        bindings.rebind(action, InputBinding::Key(key));
+        if let Err(e) = bindings.save(BINDINGS_PATH) {
+            warn!("{}", e);
+        }
+    }
+
+    fn handle_menu_events(
+        mut events: EventReader<MenuEvent>,
+        mut state: ResMut<State<GameState>>,
+        mut settings: ResMut<Settings>,
+        mut rebinding: ResMut<Rebinding>,
+        mut exit: EventWriter<AppExit>,
+        mut player: Query<&mut Health, With<Player>>,
+    ) {
+        for event in events.iter() {
+            let result = match *event {
+                MenuEvent::None => Ok(()),
+                MenuEvent::Play => state.set(GameState::Main),
+                MenuEvent::Resume => state.pop(),
+                MenuEvent::OpenSettings => state.push(GameState::Settings),
+                MenuEvent::CloseSettings => {
+                    rebinding.0 = None;
+                    state.pop()
+                }
+                MenuEvent::Respawn => {
+                    for mut health in player.iter_mut() {
+                        health.current = health.max;
+                    }
+                    state.pop()
+                }
+                MenuEvent::Quit => {
+                    exit.send(AppExit);
+                    Ok(())
+                }
+                MenuEvent::ChangeVolume(change) => {
+                    //Rounded so repeated steps don't drift
+                    settings.volume = ((settings.volume + change) * 10.0).round() / 10.0;
+                    settings.volume = settings.volume.clamp(0.0, 1.0);
+                    Ok(())
+                }
+                MenuEvent::ChangeUIScale(change) => {
+                    settings.ui_scale = ((settings.ui_scale + change) * 10.0).round() / 10.0;
+                    settings.ui_scale = settings
+                        .ui_scale
+                        .clamp(UISettings::MIN_SCALE, UISettings::MAX_SCALE);
+                    Ok(())
+                }
+                MenuEvent::ToggleVsync => {
+                    settings.vsync = !settings.vsync;
+                    Ok(())
+                }
+                MenuEvent::Rebind(action) => {
+                    rebinding.0 = Some(action);
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                warn!("{}", e);
+            }
+        }
+    }
+
+    fn update_menu_ui(
+        state: Res<State<GameState>>,
+        settings: Res<Settings>,
+        bindings: Res<InputBindings>,
+        rebinding: Res<Rebinding>,
+        ui_menu: Option<Res<Binding<UIMenu>>>,
+    ) {
+        let ui_menu = match ui_menu {
+            Some(ui_menu) => ui_menu,
+            None => return,
+        };
+        //Checked against the binding too as it is created after the first state change
+        let screen = state.current().clone();
+        if !state.is_changed()
+            && !settings.is_changed()
+            && !bindings.is_changed()
+            && !rebinding.is_changed()
+            && ui_menu.get().screen == screen
+        {
+            return;
+        }
+        let readable = bindings
+            .bindings
+            .iter()
+            .map(|(action, action_bindings)| {
+                let names = action_bindings
+                    .iter()
+                    .map(InputBinding::name)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                (*action, names)
+            })
+            .collect();
+        let in_game = screen == GameState::Main || state.inactives().contains(&GameState::Main);
+        ui_menu.set(UIMenu {
+            screen,
+            in_game,
+            settings: settings.clone(),
+            bindings: readable,
+            rebinding: rebinding.0,
+        });
+    }
+}
+
+#[derive(Default, Debug, WidgetProps, Clone, PartialEq)]
+pub struct MenuButtonProps {
+    pub label: String,
+    pub event: MenuEvent,
+    /// Just wide enough for a single symbol like + or -
+    pub small: bool,
+    #[prop_field(Styles)]
+    pub styles: Option<Style>,
+}
+
+#[widget]
+pub fn MenuButton(props: MenuButtonProps) {
+    let scale = context.query_world::<Res<Binding<UIScale>>, _, _>(|scale| scale.clone());
+    context.bind(&scale);
+    let scale = scale.get().0;
+
+    let button_style = Style {
+        width: StyleProp::Value(Units::Pixels(
+            if props.small { 40.0 } else { 220.0 } * scale,
+        )),
+        height: StyleProp::Value(Units::Pixels(40.0 * scale)),
+        padding: StyleProp::Value(Edge::all(Units::Stretch(1.0))),
+        background_color: StyleProp::Value(Color::new(0.25, 0.22, 0.18, 1.0)),
+        ..props.styles.clone().unwrap_or_default()
+    };
+
+    let event = props.event;
+    let on_event = OnEvent::new(move |context, event_type| {
+        if let EventType::Click(..) = event_type.event_type {
+            context.query_world::<EventWriter<MenuEvent>, _, _>(move |mut events| {
+                events.send(event);
+            });
+        }
+    });
+
+    let label = props.label.clone();
+    rsx! {
+        <Button on_event={Some(on_event)} styles={Some(button_style)}>
+            <Text content={label} size={20.0 * scale} />
+        </Button>
+    }
+}
+
+/// Every menu screen, drawn over the game when it is paused or over
+#[widget]
+pub fn MenuUI() {
+    let ui_menu = context.query_world::<Res<Binding<UIMenu>>, _, _>(|menu| menu.clone());
+    context.bind(&ui_menu);
+    let menu = ui_menu.get();
+
+    let backdrop = |alpha: f32| Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        left: StyleProp::Value(Units::Pixels(0.0)),
+        top: StyleProp::Value(Units::Pixels(0.0)),
+        width: StyleProp::Value(Units::Stretch(1.0)),
+        height: StyleProp::Value(Units::Stretch(1.0)),
+        padding: StyleProp::Value(Edge::all(Units::Stretch(1.0))),
+        row_between: StyleProp::Value(Units::Pixels(12.0)),
+        background_color: StyleProp::Value(Color::new(0.1, 0.1, 0.1, alpha)),
+        ..Default::default()
+    };
+
+    let (title, alpha, buttons) = match menu.screen {
+        GameState::MainMenu => (
+            "DST clone",
+            1.0,
+            vec![
+                ("Play", MenuEvent::Play),
+                ("Settings", MenuEvent::OpenSettings),
+                ("Quit", MenuEvent::Quit),
+            ],
+        ),
+        GameState::Paused => (
+            "Paused",
+            0.6,
+            vec![
+                ("Resume", MenuEvent::Resume),
+                ("Settings", MenuEvent::OpenSettings),
+                ("Quit", MenuEvent::Quit),
+            ],
+        ),
+        GameState::GameOver => (
+            "You died",
+            0.7,
+            vec![("Try again", MenuEvent::Respawn), ("Quit", MenuEvent::Quit)],
+        ),
+        _ => ("", 0.9, Vec::new()),
+    };
+    let title = title.to_string();
+
+    if menu.screen == GameState::Settings {
+        rsx! {
+            <Background styles={Some(backdrop(alpha))}>
+                <SettingsUI />
+            </Background>
+        }
+    } else if buttons.is_empty() {
+        rsx! {
+            <Element />
+        }
+    } else {
+        rsx! {
+            <Background styles={Some(backdrop(alpha))}>
+                <Text content={title} size={40.0} />
+                {VecTracker::from(buttons.iter().map(|(label, event)| {
+                    constructor! {
+                        <MenuButton label={label.to_string()} event={*event} />
+                    }
+                }))}
+            </Background>
+        }
+    }
+}
+
+/// Volume, UI scale, vsync and a row per action to rebind it
+#[widget]
+pub fn SettingsUI() {
+    let ui_menu = context.query_world::<Res<Binding<UIMenu>>, _, _>(|menu| menu.clone());
+    context.bind(&ui_menu);
+    let menu = ui_menu.get();
+
+    let row_style = Style {
+        layout_type: StyleProp::Value(LayoutType::Row),
+        width: StyleProp::Value(Units::Auto),
+        height: StyleProp::Value(Units::Auto),
+        col_between: StyleProp::Value(Units::Pixels(10.0)),
+        ..Default::default()
+    };
+    let column_style = Style {
+        width: StyleProp::Value(Units::Auto),
+        height: StyleProp::Value(Units::Auto),
+        row_between: StyleProp::Value(Units::Pixels(6.0)),
+        ..Default::default()
+    };
+    let volume = format!("Volume {:.0}%", menu.settings.volume * 100.0);
+    let scale = format!("UI scale {:.1}", menu.settings.ui_scale);
+    let vsync = format!("Vsync {}", if menu.settings.vsync { "on" } else { "off" });
+    let bindings = menu.bindings.clone();
+    let rebinding = menu.rebinding;
+
+    rsx! {
+        <Element styles={Some(column_style.clone())}>
+            <Element styles={Some(row_style.clone())}>
+                <MenuButton label={"-".to_string()} event={MenuEvent::ChangeVolume(-0.1)} small={true} />
+                <Text content={volume} size={20.0} />
+                <MenuButton label={"+".to_string()} event={MenuEvent::ChangeVolume(0.1)} small={true} />
+            </Element>
+            <Element styles={Some(row_style.clone())}>
+                <MenuButton label={"-".to_string()} event={MenuEvent::ChangeUIScale(-0.1)} small={true} />
+                <Text content={scale} size={20.0} />
+                <MenuButton label={"+".to_string()} event={MenuEvent::ChangeUIScale(0.1)} small={true} />
+            </Element>
+            <MenuButton label={vsync} event={MenuEvent::ToggleVsync} />
+            {VecTracker::from(bindings.into_iter().map(|(action, names)| {
+                let label = if rebinding == Some(action) {
+                    format!("{:?}: press a key", action)
+                } else {
+                    format!("{:?}: {}", action, names)
+                };
+                constructor! {
+                    <MenuButton label={label} event={MenuEvent::Rebind(action)} />
+                }
+            }))}
+            <MenuButton label={"Back".to_string()} event={MenuEvent::CloseSettings} />
+        </Element>
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::{error, info, warn, App, EventReader, Plugin, Res, ResMut, Time};
+use kayak_ui::{
+    bevy::ImageManager,
+    core::{
+        constructor, rsx,
+        styles::{Edge, LayoutType, Style, StyleProp, Units},
+        widget, Binding, Bound, Color, MutableBound, VecTracker,
+    },
+    widgets::{Background, Element, Image, Text},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{game_ui::UIScale, prelude::*, GameState};
+
+/// Seconds a message stays on screen
+pub const MESSAGE_LIFETIME: f32 = 4.0;
+/// Oldest messages are pushed out once there are this many
+pub const MESSAGE_LOG_SIZE: usize = 5;
+
+pub struct MessagePlugin;
+
+/// How much the player should care, picks the icon and the colour of the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Something the player should be told about, send it as an event to show it in the
+/// message log
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameMessage {
+    pub text: String,
+    pub severity: Severity,
+}
+
+impl GameMessage {
+    pub fn info(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            severity: Severity::Info,
+        }
+    }
+
+    pub fn warning(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            severity: Severity::Warning,
+        }
+    }
+
+    pub fn error(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            severity: Severity::Error,
+        }
+    }
+}
+
+/// Things the player did wrong are warnings, anything else means the game itself is broken
+impl From<GameError> for GameMessage {
+    fn from(error: GameError) -> Self {
+        match error.error_type {
+            GameErrorType::ItemMissing
+            | GameErrorType::CraftingFailed
+            | GameErrorType::EquipFailed => GameMessage::warning(error.error_payload),
+            _ => GameMessage::error(error.error_payload),
+        }
+    }
+}
+
+/// A message on screen, repeats of the last message are counted instead of stacking up
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoggedMessage {
+    pub message: GameMessage,
+    pub repeats: usize,
+    pub remaining: f32,
+}
+
+/// Every message still on screen, oldest first
+#[derive(Default)]
+pub struct MessageLog {
+    pub messages: VecDeque<LoggedMessage>,
+}
+
+/// Snapshot of the message log for the UI
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct UIMessages(pub Vec<(GameMessage, usize)>);
+
+impl Plugin for MessagePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MessageLog>()
+            .add_event::<GameMessage>()
+            .add_system(Self::collect_messages)
+            //Messages stay up behind the pause menu until play carries on
+            .add_system_set(
+                SystemSet::on_update(GameState::Main).with_system(Self::expire_messages),
+            )
+            .add_system(Self::update_message_ui);
+    }
+}
+
+impl MessageLog {
+    pub fn push(&mut self, message: GameMessage) {
+        if let Some(last) = self.messages.back_mut() {
+            if last.message == message {
+                last.repeats += 1;
+                last.remaining = MESSAGE_LIFETIME;
+                return;
+            }
+        }
+        self.messages.push_back(LoggedMessage {
+            message,
+            repeats: 1,
+            remaining: MESSAGE_LIFETIME,
+        });
+        while self.messages.len() > MESSAGE_LOG_SIZE {
+            self.messages.pop_front();
+        }
+    }
+}
+
+impl MessagePlugin {
+    /// Messages still go to the console so nothing is lost once they fade
+    fn collect_messages(mut messages: EventReader<GameMessage>, mut log: ResMut<MessageLog>) {
+        for message in messages.iter() {
+            match message.severity {
+                Severity::Info => info!("{}", message.text),
+                Severity::Warning => warn!("{}", message.text),
+                Severity::Error => error!("{}", message.text),
+            }
+            log.push(message.clone());
+        }
+    }
+
+    fn expire_messages(time: Res<Time>, mut log: ResMut<MessageLog>) {
+        if log.messages.is_empty() {
+            return;
+        }
+        let delta = time.delta_seconds();
+        for logged in log.messages.iter_mut() {
+            logged.remaining -= delta;
+        }
+        log.messages.retain(|logged| logged.remaining > 0.0);
+    }
+
+    fn update_message_ui(log: Res<MessageLog>, ui_messages: Option<Res<Binding<UIMessages>>>) {
+        let ui_messages = match ui_messages {
+            Some(ui_messages) => ui_messages,
+            None => return,
+        };
+        let messages = UIMessages(
+            log.messages
+                .iter()
+                .map(|logged| (logged.message.clone(), logged.repeats))
+                .collect(),
+        );
+        //The timers change every frame but the text only when a message comes or goes
+        if ui_messages.get() != messages {
+            ui_messages.set(messages);
+        }
+    }
+}
+
+/// Recent messages with an icon for how serious they are, newest at the bottom
+#[widget]
+pub fn MessageLogUI() {
+    let messages =
+        context.query_world::<Res<Binding<UIMessages>>, _, _>(|messages| messages.clone());
+    context.bind(&messages);
+    let scale = context.query_world::<Res<Binding<UIScale>>, _, _>(|scale| scale.clone());
+    context.bind(&scale);
+    let scale = scale.get().0;
+
+    let column_style = Style {
+        row_between: StyleProp::Value(Units::Pixels(4.0 * scale)),
+        width: StyleProp::Value(Units::Auto),
+        height: StyleProp::Value(Units::Auto),
+        ..Default::default()
+    };
+
+    let row_style = Style {
+        layout_type: StyleProp::Value(LayoutType::Row),
+        col_between: StyleProp::Value(Units::Pixels(6.0 * scale)),
+        padding: StyleProp::Value(Edge::all(Units::Pixels(4.0 * scale))),
+        width: StyleProp::Value(Units::Auto),
+        height: StyleProp::Value(Units::Auto),
+        background_color: StyleProp::Value(Color::new(0.1, 0.1, 0.1, 0.6)),
+        ..Default::default()
+    };
+
+    let icon_style = Style {
+        width: StyleProp::Value(Units::Pixels(16.0 * scale)),
+        height: StyleProp::Value(Units::Pixels(16.0 * scale)),
+        ..Default::default()
+    };
+
+    let rows: Vec<(u16, String, Style)> = messages
+        .get()
+        .0
+        .iter()
+        .map(|(message, repeats)| {
+            let handle = context.query_world::<(ResMut<ImageManager>, Res<Graphics>), _, _>(
+                |(mut manager, graphics)| manager.get(&graphics.message_icons[&message.severity]),
+            );
+            let content = match repeats {
+                1 => message.text.clone(),
+                repeats => format!("{} (x{})", message.text, repeats),
+            };
+            let text_style = Style {
+                color: StyleProp::Value(match message.severity {
+                    Severity::Info => Color::new(1.0, 1.0, 1.0, 1.0),
+                    Severity::Warning => Color::new(1.0, 0.85, 0.4, 1.0),
+                    Severity::Error => Color::new(1.0, 0.4, 0.4, 1.0),
+                }),
+                ..Default::default()
+            };
+            (handle, content, text_style)
+        })
+        .collect();
+
+    rsx! {
+        <Element styles={Some(column_style)}>
+            {VecTracker::from(rows.iter().map(|(handle, content, text_style)| {
+                constructor! {
+                    <Background styles={Some(row_style.clone())}>
+                        <Image handle={*handle} styles={Some(icon_style.clone())} />
+                        <Text content={content.clone()} size={16.0 * scale} styles={Some(text_style.clone())} />
+                    </Background>
+                }
+            }))}
+        </Element>
+    }
+}
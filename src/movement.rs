@@ -0,0 +1,368 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+
+use crate::{terrain::Terrain, GameState};
+
+pub struct MovementPlugin;
+
+/// Movement is integrated in steps of exactly this many seconds so it behaves the same
+/// at any frame rate
+pub const MOVEMENT_TIMESTEP: f32 = 1.0 / 60.0;
+/// Stops a long frame (or breakpoint) from simulating forever to catch up
+const MAX_STEPS_PER_FRAME: usize = 10;
+
+/// Anything that walks around, set input each frame and the movement systems do the rest
+#[derive(Component, Inspectable, Clone, Default)]
+pub struct MovementController {
+    /// Desired direction, anything longer than 1 is normalized so diagonals aren't faster
+    pub input: Vec2,
+    pub velocity: Vec2,
+    /// Tiles per second at full input
+    pub max_speed: f32,
+    /// How fast velocity approaches the target speed while there is input
+    pub acceleration: f32,
+    /// How fast velocity falls to zero without input
+    pub friction: f32,
+}
+
+/// Multipliers on top of max_speed, terrain is set from whatever the entity stands on,
+/// equipment from whatever it wears and effects by the weather
+#[derive(Component, Clone)]
+pub struct SpeedModifiers {
+    pub terrain: f32,
+    pub equipment: f32,
+    pub effects: Vec<SpeedEffect>,
+}
+
+/// What caused a status effect, each source has at most one effect at a time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeedSource {
+    /// Numb from being below the cold threshold
+    Cold,
+    /// Soaked by rain, wears off a while after it stops
+    Wet,
+}
+
+/// A status effect changing speed, lasts until removed if there is no timer
+#[derive(Clone)]
+pub struct SpeedEffect {
+    pub source: SpeedSource,
+    pub multiplier: f32,
+    pub timer: Option<Timer>,
+}
+
+/// Leftover frame time which hasn't been simulated yet
+#[derive(Default)]
+struct MovementAccumulator(f32);
+
+impl Plugin for MovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MovementAccumulator>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Main)
+                    .with_system(Self::tick_speed_effects)
+                    .with_system(Self::update_terrain_speed)
+                    .with_system(
+                        Self::apply_movement
+                            .label("movement")
+                            .after(Self::tick_speed_effects)
+                            .after(Self::update_terrain_speed),
+                    ),
+            )
+            .register_inspectable::<MovementController>();
+    }
+}
+
+impl MovementController {
+    pub fn new(max_speed: f32, acceleration: f32, friction: f32) -> Self {
+        Self {
+            max_speed,
+            acceleration,
+            friction,
+            ..Default::default()
+        }
+    }
+
+    /// Advances the velocity by one step and returns how far to move, pure so it can be
+    /// driven without an app
+    pub fn step(&mut self, speed_multiplier: f32, dt: f32) -> Vec2 {
+        let input = self.input.clamp_length_max(1.0);
+        let target = input * self.max_speed * speed_multiplier;
+        let rate = if input == Vec2::ZERO {
+            self.friction
+        } else {
+            self.acceleration
+        };
+
+        let difference = target - self.velocity;
+        let max_change = rate * dt;
+        if difference.length() > max_change {
+            self.velocity += difference.normalize() * max_change;
+        } else {
+            self.velocity = target;
+        }
+        self.velocity * dt
+    }
+}
+
+impl Default for SpeedModifiers {
+    fn default() -> Self {
+        Self {
+            terrain: 1.0,
+            equipment: 1.0,
+            effects: Vec::new(),
+        }
+    }
+}
+
+impl SpeedModifiers {
+    pub fn multiplier(&self) -> f32 {
+        self.effects
+            .iter()
+            .fold(self.terrain * self.equipment, |total, effect| {
+                total * effect.multiplier
+            })
+    }
+
+    pub fn effect(&self, source: SpeedSource) -> Option<&SpeedEffect> {
+        self.effects.iter().find(|effect| effect.source == source)
+    }
+
+    /// Starts an effect or replaces the one from the same source, restarting its timer
+    pub fn set_effect(&mut self, source: SpeedSource, multiplier: f32, duration: Option<f32>) {
+        let effect = SpeedEffect {
+            source,
+            multiplier,
+            timer: duration.map(|seconds| Timer::from_seconds(seconds, false)),
+        };
+        match self
+            .effects
+            .iter_mut()
+            .find(|effect| effect.source == source)
+        {
+            Some(existing) => *existing = effect,
+            None => self.effects.push(effect),
+        }
+    }
+
+    pub fn remove_effect(&mut self, source: SpeedSource) {
+        self.effects.retain(|effect| effect.source != source);
+    }
+}
+
+/// How many fixed steps fit in the time since last frame, whatever doesn't fit is kept
+/// for the next one
+fn fixed_steps(accumulator: &mut f32, delta: f32) -> usize {
+    *accumulator += delta;
+    let mut steps = 0;
+    while *accumulator >= MOVEMENT_TIMESTEP && steps < MAX_STEPS_PER_FRAME {
+        *accumulator -= MOVEMENT_TIMESTEP;
+        steps += 1;
+    }
+    if steps == MAX_STEPS_PER_FRAME {
+        *accumulator = 0.0;
+    }
+    steps
+}
+
+/// Slides along anything that can't be walked on instead of stopping dead
+fn walkable_displacement(terrain: &Terrain, position: Vec2, displacement: Vec2) -> Vec2 {
+    if terrain.is_walkable(position + displacement) {
+        return displacement;
+    }
+    let along_x = Vec2::new(displacement.x, 0.0);
+    if displacement.x != 0.0 && terrain.is_walkable(position + along_x) {
+        return along_x;
+    }
+    let along_y = Vec2::new(0.0, displacement.y);
+    if displacement.y != 0.0 && terrain.is_walkable(position + along_y) {
+        return along_y;
+    }
+    Vec2::ZERO
+}
+
+impl MovementPlugin {
+    /// Runs as many fixed steps as fit in the time since last frame
+    fn apply_movement(
+        time: Res<Time>,
+        terrain: Option<Res<Terrain>>,
+        mut accumulator: ResMut<MovementAccumulator>,
+        mut movers: Query<(
+            &mut Transform,
+            &mut MovementController,
+            Option<&SpeedModifiers>,
+        )>,
+    ) {
+        let steps = fixed_steps(&mut accumulator.0, time.delta_seconds());
+
+        for (mut transform, mut controller, modifiers) in movers.iter_mut() {
+            let multiplier = modifiers.map(|m| m.multiplier()).unwrap_or(1.0);
+            for _ in 0..steps {
+                let mut displacement = controller.step(multiplier, MOVEMENT_TIMESTEP);
+                if let Some(terrain) = terrain.as_ref() {
+                    let wanted = displacement;
+                    displacement =
+                        walkable_displacement(terrain, transform.translation.truncate(), wanted);
+                    //Walking into water kills the speed in that direction
+                    if displacement.x != wanted.x {
+                        controller.velocity.x = 0.0;
+                    }
+                    if displacement.y != wanted.y {
+                        controller.velocity.y = 0.0;
+                    }
+                }
+                transform.translation += displacement.extend(0.0);
+            }
+        }
+    }
+
+    fn tick_speed_effects(time: Res<Time>, mut modifiers_query: Query<&mut SpeedModifiers>) {
+        for mut modifiers in modifiers_query.iter_mut() {
+            if modifiers
+                .effects
+                .iter()
+                .all(|effect| effect.timer.is_none())
+            {
+                continue;
+            }
+            for effect in modifiers.effects.iter_mut() {
+                if let Some(timer) = effect.timer.as_mut() {
+                    timer.tick(time.delta());
+                }
+            }
+            modifiers.effects.retain(|effect| {
+                effect
+                    .timer
+                    .as_ref()
+                    .map(|timer| !timer.finished())
+                    .unwrap_or(true)
+            });
+        }
+    }
+
+    /// Roads are fast and sand is slow, whatever tile an entity stands on sets its speed
+    fn update_terrain_speed(
+        terrain: Option<Res<Terrain>>,
+        mut movers: Query<(&Transform, &mut SpeedModifiers)>,
+    ) {
+        for (transform, mut modifiers) in movers.iter_mut() {
+            let position = transform.translation.truncate();
+            let terrain = terrain
+                .as_ref()
+                .and_then(|terrain| terrain.tile_at(position))
+                .map(|tile| tile.speed_multiplier())
+                .unwrap_or(1.0);
+            if modifiers.terrain != terrain {
+                modifiers.terrain = terrain;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steers like a player would, walking, turning and letting go
+    fn input_at(step: usize) -> Vec2 {
+        match step {
+            0..=29 => Vec2::new(1.0, 0.0),
+            30..=59 => Vec2::new(1.0, 1.0),
+            60..=89 => Vec2::new(-0.5, 0.25),
+            _ => Vec2::ZERO,
+        }
+    }
+
+    /// Runs frames of the given lengths through the accumulator and the controller, input
+    /// follows the simulated step like a recorded replay. Returns the position after each step
+    fn simulate_frames(deltas: impl Iterator<Item = f32>) -> Vec<Vec2> {
+        let mut controller = MovementController::new(5.0, 40.0, 60.0);
+        let mut accumulator = 0.0;
+        let mut position = Vec2::ZERO;
+        let mut positions = Vec::new();
+        for delta in deltas {
+            for _ in 0..fixed_steps(&mut accumulator, delta) {
+                controller.input = input_at(positions.len());
+                position += controller.step(1.0, MOVEMENT_TIMESTEP);
+                positions.push(position);
+            }
+        }
+        positions
+    }
+
+    #[test]
+    fn frame_timing_does_not_change_positions() {
+        //A steady 30fps against a stuttering frame rate with frames shorter than a step
+        let steady = simulate_frames(std::iter::repeat(1.0 / 30.0).take(90));
+        let uneven = [0.004, 0.021, 0.0167, 0.05, 0.009, 0.033];
+        let stuttering = simulate_frames(uneven.iter().copied().cycle().take(120));
+
+        let steps = steady.len().min(stuttering.len());
+        assert!(steps >= 150, "only {} steps were run", steps);
+        assert_eq!(steady[..steps], stuttering[..steps]);
+    }
+
+    #[test]
+    fn speeds_up_to_max_and_stops_without_input() {
+        let mut controller = MovementController::new(5.0, 40.0, 60.0);
+        controller.input = Vec2::new(1.0, 0.0);
+        for _ in 0..60 {
+            controller.step(1.0, MOVEMENT_TIMESTEP);
+        }
+        assert_eq!(controller.velocity, Vec2::new(5.0, 0.0));
+
+        controller.input = Vec2::ZERO;
+        for _ in 0..60 {
+            controller.step(1.0, MOVEMENT_TIMESTEP);
+        }
+        assert_eq!(controller.velocity, Vec2::ZERO);
+    }
+
+    #[test]
+    fn diagonals_are_not_faster() {
+        let mut controller = MovementController::new(5.0, 1000.0, 1000.0);
+        controller.input = Vec2::new(1.0, 1.0);
+        controller.step(1.0, MOVEMENT_TIMESTEP);
+        assert!((controller.velocity.length() - 5.0).abs() < 1e-4);
+    }
+
+    /// Frame rate only changes how steps are grouped, never how many are run
+    #[test]
+    fn frame_rate_does_not_change_the_step_count() {
+        for frame_time in [1.0 / 30.0, 1.0 / 60.0, 1.0 / 144.0, 0.007] {
+            let mut accumulator = 0.0;
+            let frames = (1.0 / frame_time) as usize;
+            let steps: usize = (0..frames)
+                .map(|_| fixed_steps(&mut accumulator, frame_time))
+                .sum();
+            let simulated = steps as f32 * MOVEMENT_TIMESTEP + accumulator;
+            assert!(
+                (simulated - frames as f32 * frame_time).abs() < 1e-3,
+                "{} frames of {}",
+                frames,
+                frame_time
+            );
+        }
+    }
+
+    #[test]
+    fn long_frames_are_capped() {
+        let mut accumulator = 0.0;
+        assert_eq!(fixed_steps(&mut accumulator, 5.0), MAX_STEPS_PER_FRAME);
+        assert_eq!(accumulator, 0.0);
+    }
+
+    #[test]
+    fn effects_replace_the_same_source() {
+        let mut modifiers = SpeedModifiers::default();
+        modifiers.set_effect(SpeedSource::Cold, 0.5, None);
+        modifiers.set_effect(SpeedSource::Cold, 0.8, None);
+        modifiers.set_effect(SpeedSource::Wet, 0.5, Some(10.0));
+        assert_eq!(modifiers.effects.len(), 2);
+        assert!((modifiers.multiplier() - 0.4).abs() < 1e-6);
+
+        modifiers.remove_effect(SpeedSource::Cold);
+        assert!(modifiers.effect(SpeedSource::Cold).is_none());
+        assert!((modifiers.multiplier() - 0.5).abs() < 1e-6);
+    }
+}
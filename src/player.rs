@@ -1,37 +1,37 @@
 use bevy::prelude::*;
-use bevy::sprite::Anchor;
 use bevy_inspector_egui::{Inspectable, RegisterInspectable};
 
-use crate::{build::PlaceableGhost, prelude::*, GameState};
+use crate::{
+    animation::{AnimatedSprite, AnimationState, Animator, Facing},
+    build::PlaceableGhost,
+    crafting::CraftingQueue,
+    inventory::Container,
+    prelude::*,
+    GameState,
+};
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_enter(GameState::Main).with_system(Self::spawn_player.after("graphics")),
-        )
-        .add_system_set(
-            SystemSet::on_update(GameState::Main)
-                .with_system(Self::player_movement)
-                .with_system(Self::player_pickup)
-                .with_system(change_tool),
-        )
-        .register_inspectable::<Hands>()
-        .register_inspectable::<Player>();
+        app.add_system_set(SystemSet::on_enter(GameState::Main).with_system(Self::spawn_player))
+            .add_system_set(
+                SystemSet::on_update(GameState::Main)
+                    .with_system(Self::player_movement.before("movement"))
+                    .with_system(Self::player_pickup)
+                    .with_system(
+                        Self::player_animation
+                            .after("movement")
+                            .after(Self::player_pickup)
+                            .before("animation"),
+                    ),
+            )
+            .register_inspectable::<Player>();
     }
 }
 
-#[derive(Component, Inspectable, Default)]
-pub struct Hands {
-    pub tool: Option<Tool>,
-}
-#[derive(Component)]
-pub struct HandsBox;
-
 #[derive(Component, Inspectable)]
 pub struct Player {
-    speed: f32,
     arm_length: f32,
 }
 
@@ -39,45 +39,66 @@ impl PlayerPlugin {
     //XXX is this better to be 2 systems... its a bit much
     fn player_pickup(
         mut commands: Commands,
-        keyboard: Res<Input<KeyCode>>,
-        mut player_query: Query<(&Transform, &Player, &mut Inventory, &Hands)>,
+        actions: Res<ActionState>,
+        mut player_query: Query<(
+            &Transform,
+            &Player,
+            &mut Inventory,
+            &mut Equipment,
+            &mut Animator,
+        )>,
         pickupable_query: Query<
             (
                 Entity,
                 &Transform,
                 Option<&Pickupable>,
                 Option<&Harvestable>,
+                Option<&Container>,
             ),
             //XXX assuming anything with pickup is never harvestable
             //How to enforce these components are mutually exclusive
             (Or<(With<Pickupable>, With<Harvestable>)>, Without<Player>),
         >,
         graphics: Res<Graphics>,
+        mut messages: EventWriter<GameMessage>,
     ) {
-        let (player_transform, player, mut inventory, hands) = player_query.single_mut();
-        //Press space to pickup items
+        let (player_transform, player, mut inventory, mut equipment, mut animator) =
+            player_query.single_mut();
+        //Interact to pickup items
         //TODO if held walk to nearest
-        if !keyboard.just_pressed(KeyCode::Space) {
+        if !actions.just_pressed(InputAction::Interact) {
             return;
         }
-        if let Some((ent, transform, pickup, harvest)) = pickupable_query
+        if let Some((ent, transform, pickup, harvest, container)) = pickupable_query
             .iter()
-            .filter_map(|(ent, transform, pickup, harvest)| {
+            .filter_map(|(ent, transform, pickup, harvest, container)| {
                 let distance = transform
                     .translation
                     .truncate()
                     .distance(player_transform.translation.truncate());
                 if player.arm_length > distance {
-                    Some((ent, transform, distance, pickup, harvest))
+                    Some((ent, transform, distance, pickup, harvest, container))
                 } else {
                     None
                 }
             })
             //.filter(|(_, _, pickup)| can_pickup(&inventory, pickup.item))
             .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Greater))
-            .map(|(ent, transform, _, pickup, harvest)| (ent, transform, pickup, harvest))
+            .map(|(ent, transform, _, pickup, harvest, container)| {
+                (ent, transform, pickup, harvest, container)
+            })
         {
-            if let Some(pickup) = pickup {
+            //A full backpack is put straight on so its contents have somewhere to go
+            let contents = container.filter(|container| !container.is_empty());
+            if let (Some(pickup), Some(contents)) = (pickup, contents) {
+                match equipment.wear(&mut inventory, pickup.item, &contents.items) {
+                    Ok(()) => {
+                        commands.entity(ent).despawn_recursive();
+                        animator.play_once(AnimationState::PickUp);
+                    }
+                    Err(e) => messages.send(e.into()),
+                }
+            } else if let Some(pickup) = pickup {
                 let pickup_and_count = ItemAndCount {
                     item: pickup.item,
                     count: 1,
@@ -85,8 +106,12 @@ impl PlayerPlugin {
                 if inventory.can_add(&pickup_and_count) {
                     inventory.add(&pickup_and_count);
                     commands.entity(ent).despawn_recursive();
+                    animator.play_once(AnimationState::PickUp);
                 } else {
-                    info!("no available slot for item: {}", pickup_and_count);
+                    messages.send(GameMessage::warning(format!(
+                        "No room for {}",
+                        pickup_and_count.item.name()
+                    )));
                 }
             }
             if let Some(harvest) = harvest {
@@ -95,9 +120,14 @@ impl PlayerPlugin {
                     count: 1,
                 };
                 if inventory.can_add(&harvest_and_count) {
-                    if hands.tool == harvest.tool_required || harvest.tool_required.is_none() {
+                    if equipment.tool() == harvest.tool_required || harvest.tool_required.is_none()
+                    {
                         inventory.add(&harvest_and_count);
                         commands.entity(ent).despawn_recursive();
+                        animator.play_once(match harvest.tool_required {
+                            Some(Tool::Axe) => AnimationState::Chop,
+                            _ => AnimationState::PickUp,
+                        });
                         if let Some(new_object) = harvest.drops {
                             //Become what you always were meant to be
                             //println!("Pickupable found its new life as a {:?}", new_object);
@@ -107,40 +137,60 @@ impl PlayerPlugin {
                                 transform.translation.truncate(),
                             );
                         }
+                    } else if let Some(tool) = harvest.tool_required {
+                        messages.send(GameMessage::info(format!(
+                            "Hold the {:?} to harvest that",
+                            tool
+                        )));
                     }
                 } else {
-                    info!("no available slot for item: {}", harvest_and_count);
+                    messages.send(GameMessage::warning(format!(
+                        "No room for {}",
+                        harvest_and_count.item.name()
+                    )));
                 }
             }
         }
     }
 
+    /// Only steers the player, the MovementPlugin moves it on a fixed timestep
     fn player_movement(
-        keyboard: Res<Input<KeyCode>>,
-        time: Res<Time>,
-        mut player_query: Query<(&mut Transform, &Player)>,
+        actions: Res<ActionState>,
+        mut player_query: Query<&mut MovementController, With<Player>>,
     ) {
-        let (mut player_transform, player) = player_query.single_mut();
-
-        if keyboard.pressed(KeyCode::A) {
-            player_transform.translation.x -= player.speed * time.delta_seconds();
-        }
-        if keyboard.pressed(KeyCode::D) {
-            player_transform.translation.x += player.speed * time.delta_seconds();
+        let mut controller = player_query.single_mut();
+        if controller.input != actions.movement() {
+            controller.input = actions.movement();
         }
-        if keyboard.pressed(KeyCode::W) {
-            player_transform.translation.y += player.speed * time.delta_seconds();
+    }
+
+    /// Walks while moving and faces where the player is going, actions like chopping
+    /// are played as one shots where they happen
+    fn player_animation(
+        mut player_query: Query<(&MovementController, &mut Animator), With<Player>>,
+    ) {
+        let (controller, mut animator) = player_query.single_mut();
+        //Facing follows the input so turning is instant even while still sliding
+        if let Some(facing) = Facing::from_direction(controller.input) {
+            if animator.facing != facing {
+                animator.facing = facing;
+            }
         }
-        if keyboard.pressed(KeyCode::S) {
-            player_transform.translation.y -= player.speed * time.delta_seconds();
+        let state = if controller.velocity.length() > 0.1 {
+            AnimationState::Walk
+        } else {
+            AnimationState::Idle
+        };
+        if animator.state != state {
+            animator.set_state(state);
         }
     }
 
     /// Creates the single player entity, add all new player components here
     fn spawn_player(mut commands: Commands, graphics: Res<Graphics>) {
-        let mut sprite = TextureAtlasSprite::new(graphics.player_index);
-        sprite.custom_size = Some(Vec2::splat(1.));
-        sprite.anchor = Anchor::Custom(Vec2::new(0.0, 0.5 - 30.0 / 32.0));
+        let sprite = graphics
+            .animation_sprite(AnimatedSprite::Player)
+            .expect("No idle animation for the player");
         commands
             .spawn_bundle(SpriteSheetBundle {
                 sprite,
@@ -151,54 +201,17 @@ impl PlayerPlugin {
                 },
                 ..Default::default()
             })
-            .insert(Player {
-                speed: 3.0,
-                arm_length: 1.0,
-            })
+            .insert(Player { arm_length: 1.0 })
+            .insert(Animator::new(AnimatedSprite::Player))
+            .insert(MovementController::new(3.0, 30.0, 20.0))
+            .insert(SpeedModifiers::default())
             .insert(Inventory::default())
-            .insert(Hands { tool: None })
-            .insert(PlaceableGhost { to_place: None })
+            .insert(Equipment::default())
+            .insert(Insulation::default())
+            .insert(PlaceableGhost::default())
+            .insert(CraftingQueue::default())
+            .insert(Health::new(100.0))
+            .insert(Temperature::default())
             .insert(Name::new("Player"));
     }
 }
-
-/// A system which handles the ui events clicking on tools in the inventory
-pub fn change_tool(
-    mut event_reader: EventReader<UIEvent>,
-    mut query: Query<(&mut Inventory, &mut Hands), With<Player>>,
-) {
-    let mut opt_tool: Option<Tool> = None;
-    for ev in event_reader.iter() {
-        if let UIEventType::InventoryEvent(item) = ev.0 {
-            opt_tool = match item.item {
-                ItemType::Tool(t) => Some(t),
-                _ => None,
-            };
-        }
-    }
-
-    if opt_tool.is_none() {
-        return;
-    };
-
-    let (mut inventory, mut hands) = query.single_mut();
-    if hands.tool.is_some() {
-        if !inventory.can_add(&ItemAndCount {
-            item: ItemType::Tool(hands.tool.unwrap()),
-            count: 1,
-        }) {
-            return;
-        };
-        inventory.add(&ItemAndCount {
-            item: ItemType::Tool(hands.tool.unwrap()),
-            count: 1,
-        });
-    }
-
-    hands.tool = opt_tool;
-    let result = inventory.remove(&ItemAndCount {
-        item: ItemType::Tool(opt_tool.unwrap()),
-        count: 1,
-    });
-    info!("{:?}", result);
-}
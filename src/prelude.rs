@@ -1,12 +1,21 @@
+use crate::animation;
+pub use animation::AnimationPlugin;
+
 use crate::assets;
 pub use assets::{GameAssetsPlugin, Graphics, TILE_SIZE};
 
+use crate::calendar;
+pub use calendar::{CalendarPlugin, Season, WorldClock};
+
 use crate::crafting;
 pub use crafting::CraftingPlugin;
 
 use crate::game_camera;
 pub use game_camera::{CameraFollower, GameCameraPlugin};
 
+use crate::game_input;
+pub use game_input::{ActionState, GameInputPlugin, InputAction};
+
 use crate::inventory;
 pub use inventory::{Inventory, InventoryPlugin};
 
@@ -14,19 +23,41 @@ use crate::player;
 pub use player::{Player, PlayerPlugin};
 
 use crate::item;
-pub use item::{Harvestable, ItemAndCount, ItemType, ItemsPlugin, Pickupable, Tool, WorldObject};
+pub use item::{
+    Harvestable, ItemAndCount, ItemType, ItemsPlugin, Pickupable, Tool, Wearable, WorldObject,
+};
 
 use crate::mouse;
 pub use mouse::{MousePlugin, MousePosition};
 
+use crate::movement;
+pub use movement::{MovementController, MovementPlugin, SpeedModifiers, SpeedSource};
+
+use crate::equipment;
+pub use equipment::{EquipSlot, Equipment, EquipmentPlugin};
+
 use crate::error;
 pub use error::{GameError, GameErrorType};
 
-use crate::fire_graphics;
-pub use fire_graphics::FireGraphicsPlugin;
+use crate::messages;
+pub use messages::{GameMessage, MessagePlugin, Severity};
+
+use crate::lighting;
+pub use lighting::{LightSource, LightingPlugin, LightingSettings, Occluder};
+
+use crate::terrain;
+pub use terrain::{Terrain, TerrainPlugin, TerrainType};
+
+use crate::stats;
+pub use stats::{Health, Insulation, StatModifiers, StatsPlugin};
+
+use crate::weather;
+pub use weather::{Temperature, Weather, WeatherKind, WeatherPlugin};
 
 use crate::game_ui_widgets;
-pub use game_ui_widgets::{HandUI, InventoryUI, ItemProps, RecipeUI};
+pub use game_ui_widgets::{
+    ClockUI, ContainerUI, EquipmentUI, InventoryUI, ItemProps, RecipeTooltip, RecipeUI,
+};
 
 use crate::game_ui;
-pub use game_ui::{GameUIPlugin, UIEvent, UIEventType, UIProps};
+pub use game_ui::{GameUIPlugin, SlotRef, UIEvent, UIEventType, UIProps};
@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::{prelude::*, window::PresentMode};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{game_ui::UISettings, prelude::*};
+
+pub struct SettingsPlugin;
+
+/// Where the players settings are stored, next to the bindings
+pub const SETTINGS_PATH: &str = "config/settings.ron";
+
+/// Everything changeable from the settings screen except the key bindings, which keep
+/// their own file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Master volume from 0 to 1, for any sound played with PlaybackSettings
+    pub volume: f32,
+    pub ui_scale: f32,
+    pub vsync: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            ui_scale: 1.0,
+            vsync: true,
+        }
+    }
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>()
+            .add_system(Self::apply_settings);
+    }
+}
+
+impl Settings {
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::Fifo
+        } else {
+            PresentMode::Immediate
+        }
+    }
+
+    /// Reads the settings file, falling back to the defaults if it is missing or broken
+    pub fn load_or_default(path: &str) -> Self {
+        let loaded = fs::read_to_string(path)
+            .map_err(|e| {
+                GameError::new(
+                    GameErrorType::AssetLoadFailed,
+                    format!("Failed to read {}: {}", path, e),
+                )
+            })
+            .and_then(|desc| {
+                ron::de::from_str::<Settings>(&desc).map_err(|e| {
+                    GameError::new(
+                        GameErrorType::AssetLoadFailed,
+                        format!("Failed to parse {}: {}", path, e),
+                    )
+                })
+            });
+
+        match loaded {
+            Ok(settings) => settings,
+            Err(e) => {
+                warn!("{}, using default settings", e);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), GameError> {
+        let to_error =
+            |e: String| GameError::new(GameErrorType::SaveFailed, format!("{}: {}", path, e));
+        let desc = ron::ser::to_string_pretty(self, PrettyConfig::default())
+            .map_err(|e| to_error(e.to_string()))?;
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent).map_err(|e| to_error(e.to_string()))?;
+        }
+        fs::write(path, desc).map_err(|e| to_error(e.to_string()))
+    }
+}
+
+impl SettingsPlugin {
+    /// Pushes changed settings out to the window and the UI and writes them to disk
+    fn apply_settings(
+        settings: Res<Settings>,
+        mut windows: ResMut<Windows>,
+        mut ui_settings: ResMut<UISettings>,
+    ) {
+        if !settings.is_changed() {
+            return;
+        }
+        if let Some(window) = windows.get_primary_mut() {
+            if window.present_mode() != settings.present_mode() {
+                window.set_present_mode(settings.present_mode());
+            }
+        }
+        if ui_settings.scale != settings.ui_scale {
+            ui_settings.scale = settings.ui_scale;
+        }
+        //The first run is just the settings being loaded
+        if !settings.is_added() {
+            if let Err(e) = settings.save(SETTINGS_PATH) {
+                warn!("{}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volume_is_saved_and_loaded() {
+        let path = std::env::temp_dir().join("bevy_survival_settings_test.ron");
+        let path = path.to_str().unwrap();
+        let settings = Settings {
+            volume: 0.3,
+            ..Default::default()
+        };
+        settings.save(path).unwrap();
+        assert_eq!(Settings::load_or_default(path), settings);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn older_files_get_the_default_volume() {
+        let settings: Settings = ron::de::from_str("(ui_scale: 1.5, vsync: false)").unwrap();
+        assert_eq!(settings.volume, 1.0);
+        assert_eq!(settings.ui_scale, 1.5);
+    }
+}
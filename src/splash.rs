@@ -0,0 +1,154 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use crate::{assets::LoadingProgress, prelude::*, GameState};
+
+pub struct SplashPlugin;
+
+/// Loaded outside the asset collections so it can show while they load
+const SPLASH_FONT: &str = "QuattrocentoSans-Regular.ttf";
+
+/// Everything spawned for the splash or error screen, despawned when the screen is left
+#[derive(Component)]
+struct ScreenEntity;
+
+/// The filled part of the loading bar
+#[derive(Component)]
+struct ProgressBar;
+
+impl Plugin for SplashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Splash).with_system(Self::spawn_splash_screen),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Splash).with_system(Self::update_progress_bar),
+        )
+        .add_system_set(SystemSet::on_exit(GameState::Splash).with_system(Self::despawn_screen))
+        .add_system_set(
+            SystemSet::on_enter(GameState::LoadError).with_system(Self::spawn_error_screen),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::LoadError).with_system(Self::quit_on_cancel),
+        );
+    }
+}
+
+fn screen_root() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            //Bevy UI lays columns out bottom to top
+            flex_direction: FlexDirection::ColumnReverse,
+            ..Default::default()
+        },
+        color: Color::rgb(0.1, 0.1, 0.1).into(),
+        ..Default::default()
+    }
+}
+
+fn text(content: &str, font: Handle<Font>, size: f32) -> TextBundle {
+    TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(10.0)),
+            ..Default::default()
+        },
+        text: Text::with_section(
+            content,
+            TextStyle {
+                font,
+                font_size: size,
+                color: Color::WHITE,
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    }
+}
+
+impl SplashPlugin {
+    fn spawn_splash_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+        let font = asset_server.load(SPLASH_FONT);
+        commands
+            .spawn_bundle(UiCameraBundle::default())
+            .insert(ScreenEntity);
+        commands
+            .spawn_bundle(screen_root())
+            .insert(ScreenEntity)
+            .insert(Name::new("SplashScreen"))
+            .with_children(|parent| {
+                parent.spawn_bundle(text("Loading", font, 40.0));
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(400.0), Val::Px(24.0)),
+                            margin: Rect::all(Val::Px(10.0)),
+                            ..Default::default()
+                        },
+                        color: Color::rgb(0.25, 0.25, 0.25).into(),
+                        ..Default::default()
+                    })
+                    .with_children(|bar| {
+                        bar.spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                                ..Default::default()
+                            },
+                            color: Color::hex("b0c060").unwrap().into(),
+                            ..Default::default()
+                        })
+                        .insert(ProgressBar);
+                    });
+            });
+    }
+
+    fn update_progress_bar(
+        progress: Res<LoadingProgress>,
+        mut bar_query: Query<&mut Style, With<ProgressBar>>,
+    ) {
+        if !progress.is_changed() {
+            return;
+        }
+        for mut style in bar_query.iter_mut() {
+            style.size.width = Val::Percent(progress.fraction() * 100.0);
+        }
+    }
+
+    fn spawn_error_screen(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        progress: Res<LoadingProgress>,
+    ) {
+        let font = asset_server.load(SPLASH_FONT);
+        let reason = progress
+            .error
+            .clone()
+            .unwrap_or_else(|| "Unknown error".to_string());
+        commands
+            .spawn_bundle(UiCameraBundle::default())
+            .insert(ScreenEntity);
+        commands
+            .spawn_bundle(screen_root())
+            .insert(ScreenEntity)
+            .insert(Name::new("ErrorScreen"))
+            .with_children(|parent| {
+                parent.spawn_bundle(text("The game failed to load", font.clone(), 40.0));
+                parent.spawn_bundle(text(&reason, font.clone(), 20.0));
+                parent.spawn_bundle(text("Press Escape to quit", font, 20.0));
+            });
+    }
+
+    fn despawn_screen(mut commands: Commands, screen_query: Query<Entity, With<ScreenEntity>>) {
+        for entity in screen_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    fn quit_on_cancel(actions: Res<ActionState>, mut exit: EventWriter<AppExit>) {
+        if actions.just_pressed(InputAction::Cancel) {
+            exit.send(AppExit);
+        }
+    }
+}
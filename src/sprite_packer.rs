@@ -0,0 +1,133 @@
+use std::cmp::Ordering;
+
+use crate::prelude::*;
+
+/// Places rectangles onto a sheet of a fixed width in rows (shelves), tallest first.
+/// Used by the pack_sprites tool so it needs no bevy app
+pub struct SpritePacker {
+    pub width: u32,
+    /// Empty pixels left around every sprite to stop neighbours bleeding through
+    pub padding: u32,
+}
+
+/// Where every sprite ended up, in the same order as the sizes given to pack
+pub struct PackedSheet {
+    pub positions: Vec<(u32, u32)>,
+    /// Rounded up to a power of two
+    pub height: u32,
+}
+
+impl SpritePacker {
+    pub fn pack(&self, sizes: &[(u32, u32)]) -> Result<PackedSheet, GameError> {
+        let mut order: Vec<usize> = (0..sizes.len()).collect();
+        //Ties keep their input order so packing the same files always gives the same sheet
+        order.sort_by(|a, b| sizes[*b].1.cmp(&sizes[*a].1).then(a.cmp(b)));
+
+        let mut positions = vec![(0, 0); sizes.len()];
+        let (mut x, mut y, mut shelf_height) = (self.padding, self.padding, 0);
+        for index in order {
+            let (width, height) = sizes[index];
+            if width + self.padding * 2 > self.width {
+                return Err(GameError::new(
+                    GameErrorType::InvalidGraphics,
+                    format!(
+                        "A {}x{} sprite does not fit in a sheet {} wide",
+                        width, height, self.width
+                    ),
+                ));
+            }
+            if x + width + self.padding > self.width {
+                x = self.padding;
+                y += shelf_height + self.padding;
+                shelf_height = 0;
+            }
+            positions[index] = (x, y);
+            x += width + self.padding;
+            shelf_height = shelf_height.max(height);
+        }
+
+        let used_height = y + shelf_height + self.padding;
+        Ok(PackedSheet {
+            positions,
+            height: used_height.next_power_of_two(),
+        })
+    }
+}
+
+/// Compares names the way people count, so frame 2.png comes before 10.png
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        if a.is_empty() || b.is_empty() {
+            return a.len().cmp(&b.len());
+        }
+        let (a_chunk, a_rest) = split_chunk(a);
+        let (b_chunk, b_rest) = split_chunk(b);
+        let is_number = |chunk: &str| chunk.starts_with(|c: char| c.is_ascii_digit());
+        let order = if is_number(a_chunk) && is_number(b_chunk) {
+            //Leading zeros only break ties so 01 and 1 still sort the same every time
+            let (a_value, b_value) = (
+                a_chunk.trim_start_matches('0'),
+                b_chunk.trim_start_matches('0'),
+            );
+            a_value
+                .len()
+                .cmp(&b_value.len())
+                .then_with(|| a_value.cmp(b_value))
+                .then_with(|| a_chunk.len().cmp(&b_chunk.len()))
+        } else {
+            a_chunk.cmp(b_chunk)
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+        a = a_rest;
+        b = b_rest;
+    }
+}
+
+/// Splits off the leading run of digits or non digits
+fn split_chunk(name: &str) -> (&str, &str) {
+    let digits = name.starts_with(|c: char| c.is_ascii_digit());
+    let end = name
+        .find(|c: char| c.is_ascii_digit() != digits)
+        .unwrap_or(name.len());
+    name.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_sort_by_value() {
+        let mut names = vec!["10.png", "2.png", "1.png", "0.png", "11.png", "3.png"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec!["0.png", "1.png", "2.png", "3.png", "10.png", "11.png"]
+        );
+    }
+
+    #[test]
+    fn text_and_numbers_mix() {
+        let mut names = vec!["walk10", "walk2", "idle", "walk", "Walk1", "walk02"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec!["Walk1", "idle", "walk", "walk2", "walk02", "walk10"]
+        );
+    }
+
+    #[test]
+    fn packs_tallest_first_and_repeatably() {
+        let packer = SpritePacker {
+            width: 8,
+            padding: 1,
+        };
+        let packed = packer.pack(&[(2, 2), (3, 4), (2, 2), (4, 1)]).ok().unwrap();
+        assert_eq!(packed.positions, vec![(5, 1), (1, 1), (1, 6), (1, 9)]);
+        assert_eq!(packed.height, 16);
+        assert!(packer.pack(&[(7, 1)]).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_inspectable::<Health>()
+            .register_inspectable::<Insulation>();
+    }
+}
+
+#[derive(Component, Inspectable, Clone)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+/// Degrees of cold kept out by whatever is being worn
+#[derive(Component, Inspectable, Clone, Default)]
+pub struct Insulation(pub f32);
+
+/// What an item changes while it is equipped
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StatModifiers {
+    pub insulation: f32,
+    /// Multiplier on walking speed
+    pub speed: f32,
+}
+
+impl Default for StatModifiers {
+    fn default() -> Self {
+        Self {
+            insulation: 0.0,
+            speed: 1.0,
+        }
+    }
+}
+
+impl StatModifiers {
+    pub fn combine(self, other: StatModifiers) -> StatModifiers {
+        StatModifiers {
+            insulation: self.insulation + other.insulation,
+            speed: self.speed * other.speed,
+        }
+    }
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn damage(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.0);
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
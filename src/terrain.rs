@@ -0,0 +1,532 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::render::render_resource::{
+    Extent3d, FilterMode, SamplerDescriptor, TextureDimension, TextureFormat,
+};
+use bevy_inspector_egui::Inspectable;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{prelude::*, GameState, WorldSeed};
+
+pub struct TerrainPlugin;
+
+/// Dug and placed turf is written here and loaded instead of generating new terrain
+pub const TERRAIN_SAVE_PATH: &str = "saves/terrain.ron";
+
+/// In tiles, the world is centred on the origin
+pub const TERRAIN_WIDTH: u32 = 128;
+pub const TERRAIN_HEIGHT: u32 = 128;
+/// Tiles around the origin kept dry and flat so the player never starts in a lake
+const SPAWN_CLEARING_RADIUS: f32 = 8.0;
+/// Below the objects, which sit at 0, the 2d camera can't see past -0.1
+const TERRAIN_LAYER: f32 = -0.05;
+
+#[derive(
+    Inspectable, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
+pub enum TerrainType {
+    Grass,
+    Dirt,
+    Sand,
+    Water,
+    Rock,
+    /// Only made by players from turf
+    Road,
+    /// Wet low ground, slow going and too boggy to dig
+    Marsh,
+}
+
+/// The ground under everything, one tile per world unit
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(into = "TerrainSave", try_from = "TerrainSave")]
+pub struct Terrain {
+    pub width: u32,
+    pub height: u32,
+    /// Row by row from the bottom left
+    tiles: Vec<TerrainType>,
+}
+
+/// How terrain is written to disk, one character per tile so the file stays small and
+/// can be read by eye
+#[derive(Deserialize, Serialize)]
+struct TerrainSave {
+    width: u32,
+    height: u32,
+    /// From the top row down, like the map looks in game
+    rows: Vec<String>,
+}
+
+/// The image the terrain is drawn into, one texel per tile
+struct TerrainTexture(Handle<Image>);
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Main).with_system(Self::generate_terrain),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Main)
+                .with_system(Self::redraw_terrain)
+                .with_system(Self::save_terrain),
+        );
+    }
+}
+
+impl TerrainType {
+    /// Everything which can be dug up into turf
+    pub const TURFS: [TerrainType; 4] = [
+        TerrainType::Grass,
+        TerrainType::Sand,
+        TerrainType::Rock,
+        TerrainType::Road,
+    ];
+
+    /// Multiplier on the speed of anything walking over it, dirt paths make roads
+    pub fn speed_multiplier(self) -> f32 {
+        match self {
+            TerrainType::Grass => 1.0,
+            TerrainType::Dirt => 1.25,
+            TerrainType::Sand => 0.8,
+            TerrainType::Water => 1.0,
+            TerrainType::Rock => 0.9,
+            TerrainType::Road => 1.6,
+            TerrainType::Marsh => 0.6,
+        }
+    }
+
+    /// Digging leaves bare dirt behind, which can't be dug any further
+    pub fn is_diggable(self) -> bool {
+        Self::TURFS.contains(&self)
+    }
+
+    /// Turf can be laid on bare dirt or used to fill in water
+    pub fn accepts_turf(self) -> bool {
+        matches!(self, TerrainType::Dirt | TerrainType::Water)
+    }
+
+    /// Water can't be walked on, it is only fished from the shore
+    pub fn is_walkable(self) -> bool {
+        self != TerrainType::Water
+    }
+
+    pub fn can_place(self, object: WorldObject) -> bool {
+        match self {
+            TerrainType::Water => false,
+            //Plants need soil
+            TerrainType::Rock | TerrainType::Road => !object.is_plant(),
+            _ => true,
+        }
+    }
+
+    /// How fast an object regrows on this ground, 0 stops it completely
+    /// TODO it would be great to describe this outside of code, in a config or something
+    pub fn growth_multiplier(self, object: WorldObject) -> f32 {
+        match (self, object) {
+            (TerrainType::Water | TerrainType::Rock | TerrainType::Road, _) => 0.0,
+            (TerrainType::Sand, _) => 0.25,
+            (TerrainType::Dirt, WorldObject::PluckedGrass) => 0.5,
+            _ => 1.0,
+        }
+    }
+
+    fn color(self) -> [u8; 3] {
+        match self {
+            TerrainType::Grass => [0xb0, 0xc0, 0x60],
+            TerrainType::Dirt => [0x9a, 0x7a, 0x50],
+            TerrainType::Sand => [0xe0, 0xd0, 0x98],
+            TerrainType::Water => [0x48, 0x78, 0xc0],
+            TerrainType::Rock => [0x88, 0x88, 0x8c],
+            TerrainType::Road => [0x70, 0x68, 0x60],
+            TerrainType::Marsh => [0x60, 0x78, 0x58],
+        }
+    }
+
+    fn symbol(self) -> char {
+        match self {
+            TerrainType::Grass => 'g',
+            TerrainType::Dirt => 'd',
+            TerrainType::Sand => 's',
+            TerrainType::Water => 'w',
+            TerrainType::Rock => 'r',
+            TerrainType::Road => '=',
+            TerrainType::Marsh => 'm',
+        }
+    }
+
+    fn from_symbol(symbol: char) -> Option<TerrainType> {
+        match symbol {
+            'g' => Some(TerrainType::Grass),
+            'd' => Some(TerrainType::Dirt),
+            's' => Some(TerrainType::Sand),
+            'w' => Some(TerrainType::Water),
+            'r' => Some(TerrainType::Rock),
+            '=' => Some(TerrainType::Road),
+            'm' => Some(TerrainType::Marsh),
+            _ => None,
+        }
+    }
+}
+
+/// Smooth random values between 0 and 1, features are roughly cell tiles across
+fn value_noise(rng: &mut ChaCha8Rng, width: u32, height: u32, cell: u32) -> Vec<f32> {
+    let lattice_width = width / cell + 2;
+    let lattice_height = height / cell + 2;
+    let lattice: Vec<f32> = (0..lattice_width * lattice_height)
+        .map(|_| rng.gen())
+        .collect();
+    let at = |x: u32, y: u32| lattice[(y * lattice_width + x) as usize];
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+
+    let mut values = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let (fx, fy) = (x as f32 / cell as f32, y as f32 / cell as f32);
+            let (ix, iy) = (fx as u32, fy as u32);
+            let (tx, ty) = (smooth(fx.fract()), smooth(fy.fract()));
+            let bottom = at(ix, iy) * (1.0 - tx) + at(ix + 1, iy) * tx;
+            let top = at(ix, iy + 1) * (1.0 - tx) + at(ix + 1, iy + 1) * tx;
+            values.push(bottom * (1.0 - ty) + top * ty);
+        }
+    }
+    values
+}
+
+impl Terrain {
+    /// The same seed always makes the same terrain
+    pub fn generate(seed: u64, width: u32, height: u32) -> Self {
+        //Weather uses the seed and the one after it
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.wrapping_add(2));
+        let broad = value_noise(&mut rng, width, height, 16);
+        let detail = value_noise(&mut rng, width, height, 5);
+        let moisture = value_noise(&mut rng, width, height, 12);
+
+        let mut terrain = Terrain {
+            width,
+            height,
+            tiles: vec![TerrainType::Grass; (width * height) as usize],
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let index = (y * width + x) as usize;
+                let elevation = broad[index] * 0.75 + detail[index] * 0.25;
+                let mut tile = if elevation < 0.28 {
+                    TerrainType::Water
+                } else if elevation < 0.34 {
+                    TerrainType::Sand
+                } else if elevation > 0.72 {
+                    TerrainType::Rock
+                } else if elevation < 0.42 && moisture[index] > 0.65 {
+                    TerrainType::Marsh
+                } else if moisture[index] > 0.35 {
+                    TerrainType::Grass
+                } else {
+                    TerrainType::Dirt
+                };
+                if terrain.tile_centre(x, y).length() < SPAWN_CLEARING_RADIUS {
+                    tile = TerrainType::Grass;
+                }
+                terrain.tiles[index] = tile;
+            }
+        }
+        terrain
+    }
+
+    /// Which tile a position is on, None off the edge of the world
+    pub fn tile_coords(&self, position: Vec2) -> Option<(u32, u32)> {
+        let x = (position.x + 0.5).floor() as i64 + (self.width / 2) as i64;
+        let y = (position.y + 0.5).floor() as i64 + (self.height / 2) as i64;
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return None;
+        }
+        Some((x as u32, y as u32))
+    }
+
+    pub fn tile_centre(&self, x: u32, y: u32) -> Vec2 {
+        Vec2::new(
+            x as f32 - (self.width / 2) as f32,
+            y as f32 - (self.height / 2) as f32,
+        )
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<TerrainType> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.tiles[(y * self.width + x) as usize])
+    }
+
+    pub fn tile_at(&self, position: Vec2) -> Option<TerrainType> {
+        self.tile_coords(position).and_then(|(x, y)| self.get(x, y))
+    }
+
+    /// Returns false off the edge of the world
+    pub fn set_tile(&mut self, position: Vec2, tile: TerrainType) -> bool {
+        match self.tile_coords(position) {
+            Some((x, y)) => {
+                self.tiles[(y * self.width + x) as usize] = tile;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The edge of the world can't be walked past either
+    pub fn is_walkable(&self, position: Vec2) -> bool {
+        self.tile_at(position)
+            .map(|tile| tile.is_walkable())
+            .unwrap_or(false)
+    }
+
+    pub fn can_place(&self, object: WorldObject, position: Vec2) -> bool {
+        self.tile_at(position)
+            .map(|tile| tile.can_place(object))
+            .unwrap_or(false)
+    }
+
+    pub fn load(path: &str) -> Result<Self, GameError> {
+        let to_error =
+            |e: String| GameError::new(GameErrorType::SaveFailed, format!("{}: {}", path, e));
+        let save = fs::read_to_string(path).map_err(|e| to_error(e.to_string()))?;
+        ron::de::from_str(&save).map_err(|e| to_error(e.to_string()))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), GameError> {
+        let to_error =
+            |e: String| GameError::new(GameErrorType::SaveFailed, format!("{}: {}", path, e));
+        let save = ron::ser::to_string_pretty(self, PrettyConfig::default())
+            .map_err(|e| to_error(e.to_string()))?;
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent).map_err(|e| to_error(e.to_string()))?;
+        }
+        fs::write(path, save).map_err(|e| to_error(e.to_string()))
+    }
+
+    /// Texels for the terrain image, rows from the top down with a little noise so
+    /// large areas don't look flat
+    fn texels(&self) -> Vec<u8> {
+        let mut texels = Vec::with_capacity((self.width * self.height * 4) as usize);
+        for row in 0..self.height {
+            let y = self.height - 1 - row;
+            for x in 0..self.width {
+                let [r, g, b] = self.tiles[(y * self.width + x) as usize].color();
+                let hash = (x.wrapping_mul(73_856_093) ^ y.wrapping_mul(19_349_663)) % 16;
+                let shade = |channel: u8| (channel as i32 + hash as i32 - 8).clamp(0, 255) as u8;
+                texels.extend_from_slice(&[shade(r), shade(g), shade(b), 255]);
+            }
+        }
+        texels
+    }
+}
+
+impl From<Terrain> for TerrainSave {
+    fn from(terrain: Terrain) -> Self {
+        let rows = (0..terrain.height)
+            .rev()
+            .map(|y| {
+                (0..terrain.width)
+                    .map(|x| terrain.tiles[(y * terrain.width + x) as usize].symbol())
+                    .collect()
+            })
+            .collect();
+        TerrainSave {
+            width: terrain.width,
+            height: terrain.height,
+            rows,
+        }
+    }
+}
+
+impl TryFrom<TerrainSave> for Terrain {
+    type Error = String;
+
+    fn try_from(save: TerrainSave) -> Result<Self, Self::Error> {
+        //Empty terrain has no corner tiles to place the sprite between
+        if save.width == 0 || save.height == 0 {
+            return Err(format!(
+                "Terrain must be at least 1x1, found {}x{}",
+                save.width, save.height
+            ));
+        }
+        let tile_count = save
+            .width
+            .checked_mul(save.height)
+            .ok_or_else(|| format!("Terrain {}x{} is too big", save.width, save.height))?
+            as usize;
+        if save.rows.len() != save.height as usize {
+            return Err(format!(
+                "Expected {} rows of terrain, found {}",
+                save.height,
+                save.rows.len()
+            ));
+        }
+        //Rows are saved top first but tiles are stored from the bottom up
+        let mut tiles = Vec::with_capacity(tile_count);
+        for (row, line) in save.rows.iter().enumerate().rev() {
+            if line.chars().count() != save.width as usize {
+                return Err(format!("Row {} is not {} tiles wide", row, save.width));
+            }
+            for symbol in line.chars() {
+                let tile = TerrainType::from_symbol(symbol)
+                    .ok_or_else(|| format!("Unknown terrain '{}' in row {}", symbol, row))?;
+                tiles.push(tile);
+            }
+        }
+        if tiles.len() != tile_count {
+            return Err(format!(
+                "Expected {} tiles of terrain, found {}",
+                tile_count,
+                tiles.len()
+            ));
+        }
+        Ok(Terrain {
+            width: save.width,
+            height: save.height,
+            tiles,
+        })
+    }
+}
+
+impl TerrainPlugin {
+    /// Loads the saved terrain if there is one, otherwise makes new terrain from the seed
+    fn generate_terrain(
+        mut commands: Commands,
+        seed: Res<WorldSeed>,
+        mut images: ResMut<Assets<Image>>,
+    ) {
+        let terrain = if Path::new(TERRAIN_SAVE_PATH).exists() {
+            Terrain::load(TERRAIN_SAVE_PATH).unwrap_or_else(|e| {
+                warn!("{}, generating new terrain", e);
+                Terrain::generate(seed.0, TERRAIN_WIDTH, TERRAIN_HEIGHT)
+            })
+        } else {
+            Terrain::generate(seed.0, TERRAIN_WIDTH, TERRAIN_HEIGHT)
+        };
+
+        let mut image = Image::new(
+            Extent3d {
+                width: terrain.width,
+                height: terrain.height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            terrain.texels(),
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        //Hard edges between tiles
+        image.sampler_descriptor = SamplerDescriptor {
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            ..Default::default()
+        };
+        let texture = images.add(image);
+
+        let bottom_left = terrain.tile_centre(0, 0);
+        let top_right = terrain.tile_centre(terrain.width - 1, terrain.height - 1);
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(terrain.width as f32, terrain.height as f32)),
+                    ..Default::default()
+                },
+                texture: texture.clone(),
+                transform: Transform::from_translation(
+                    ((bottom_left + top_right) / 2.0).extend(TERRAIN_LAYER),
+                ),
+                ..Default::default()
+            })
+            .insert(Name::new("Terrain"));
+
+        commands.insert_resource(TerrainTexture(texture));
+        commands.insert_resource(terrain);
+    }
+
+    /// Writes the terrain out every time it is changed by the player
+    fn save_terrain(terrain: Option<Res<Terrain>>) {
+        if let Some(terrain) = terrain {
+            if terrain.is_changed() && !terrain.is_added() {
+                if let Err(e) = terrain.save(TERRAIN_SAVE_PATH) {
+                    warn!("{}", e);
+                }
+            }
+        }
+    }
+
+    /// Redraws the whole image whenever a tile changes, it is small enough not to matter
+    fn redraw_terrain(
+        terrain: Option<Res<Terrain>>,
+        texture: Option<Res<TerrainTexture>>,
+        mut images: ResMut<Assets<Image>>,
+    ) {
+        let (terrain, texture) = match (terrain, texture) {
+            (Some(terrain), Some(texture)) => (terrain, texture),
+            _ => return,
+        };
+        if !terrain.is_changed() || terrain.is_added() {
+            return;
+        }
+        if let Some(image) = images.get_mut(&texture.0) {
+            image.data = terrain.texels();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn save(width: u32, height: u32, rows: &[&str]) -> TerrainSave {
+        TerrainSave {
+            width,
+            height,
+            rows: rows.iter().map(|row| row.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_ron() {
+        let terrain = Terrain::generate(7, 12, 5);
+        let desc = ron::ser::to_string(&terrain).unwrap();
+        let loaded: Terrain = ron::de::from_str(&desc).unwrap();
+        assert_eq!((loaded.width, loaded.height), (12, 5));
+        assert_eq!(loaded.tiles, terrain.tiles);
+    }
+
+    #[test]
+    fn rows_are_stored_bottom_up() {
+        let terrain = Terrain::try_from(save(2, 2, &["gd", "sw"])).unwrap();
+        assert_eq!(terrain.get(0, 0), Some(TerrainType::Sand));
+        assert_eq!(terrain.get(1, 0), Some(TerrainType::Water));
+        assert_eq!(terrain.get(0, 1), Some(TerrainType::Grass));
+        assert_eq!(terrain.get(1, 1), Some(TerrainType::Dirt));
+    }
+
+    #[test]
+    fn empty_terrain_is_an_error() {
+        assert!(Terrain::try_from(save(0, 0, &[])).is_err());
+        assert!(Terrain::try_from(save(0, 2, &["", ""])).is_err());
+        assert!(Terrain::try_from(save(3, 0, &[])).is_err());
+    }
+
+    #[test]
+    fn wrong_sizes_are_errors() {
+        assert!(Terrain::try_from(save(2, 2, &["gg"])).is_err());
+        assert!(Terrain::try_from(save(2, 2, &["gg", "g"])).is_err());
+        assert!(Terrain::try_from(save(2, 1, &["ggg"])).is_err());
+        assert!(Terrain::try_from(save(u32::MAX, 2, &["g", "g"])).is_err());
+        assert!(Terrain::try_from(save(1, 1, &["x"])).is_err());
+    }
+
+    #[test]
+    fn marsh_is_generated_and_slow() {
+        let terrain = Terrain::generate(0x5eed, 128, 128);
+        assert!(terrain.tiles.contains(&TerrainType::Marsh));
+        assert!(TerrainType::Marsh.speed_multiplier() < TerrainType::Sand.speed_multiplier());
+        assert!(!TerrainType::Marsh.is_diggable());
+    }
+}
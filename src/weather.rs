@@ -0,0 +1,384 @@
+use bevy::prelude::*;
+use bevy::render::camera::Camera2d;
+use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{lighting::Fire, prelude::*, GameState, WorldSeed};
+
+pub struct WeatherPlugin;
+
+/// Comfortable temperature on a clear spring day, everything else is an offset from this
+pub const BASE_TEMPERATURE: f32 = 15.0;
+/// Below this the cold starts to hurt
+pub const COLD_THRESHOLD: f32 = 5.0;
+/// Health lost per second for every degree below COLD_THRESHOLD
+const COLD_DAMAGE_PER_DEGREE: f32 = 0.1;
+/// Speed lost for every degree below COLD_THRESHOLD
+const COLD_SLOWDOWN_PER_DEGREE: f32 = 0.05;
+/// Freezing never slows anything down more than this
+const MAX_COLD_SLOWDOWN: f32 = 0.5;
+/// Speed while soaked through
+const WET_SPEED: f32 = 0.9;
+/// Seconds it takes to dry out once the rain stops
+const DRYING_SECONDS: f32 = 20.0;
+/// Degrees per second a body moves towards the temperature around it
+const TEMPERATURE_CHANGE_RATE: f32 = 0.5;
+/// Warmth at the centre of a fire per point of strength, fading out at the edge of its light
+const FIRE_HEAT_PER_STRENGTH: f32 = 4.0;
+
+const PARTICLE_LAYER: f32 = 750.0;
+
+#[derive(Inspectable, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WeatherKind {
+    Clear,
+    Rain,
+    Storm,
+    Snow,
+}
+
+/// The weather everywhere in the world, changes when the timer runs out
+pub struct Weather {
+    pub kind: WeatherKind,
+    pub timer: Timer,
+}
+
+/// Weather changes and particles draw from separate generators so particles
+/// never change what the weather does
+pub struct WeatherRng {
+    weather: ChaCha8Rng,
+    particles: ChaCha8Rng,
+}
+
+/// How hot something is in degrees, moves towards the air temperature around it
+#[derive(Component, Inspectable, Clone)]
+pub struct Temperature {
+    pub current: f32,
+}
+
+#[derive(Component)]
+struct WeatherParticle {
+    velocity: Vec2,
+    lifetime: Timer,
+}
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldSeed>()
+            .init_resource::<Weather>()
+            .add_system_set(SystemSet::on_enter(GameState::Main).with_system(Self::seed_weather))
+            .add_system_set(
+                SystemSet::on_update(GameState::Main)
+                    .with_system(Self::advance_weather)
+                    .with_system(Self::rain_on_fires.after(Self::advance_weather))
+                    .with_system(Self::update_temperature.after(Self::advance_weather))
+                    .with_system(Self::cold_damage.after(Self::update_temperature))
+                    .with_system(Self::weather_slowdown.after(Self::update_temperature))
+                    .with_system(Self::spawn_particles.after(Self::advance_weather))
+                    .with_system(Self::move_particles),
+            )
+            .register_inspectable::<Temperature>();
+    }
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            kind: WeatherKind::Clear,
+            timer: Timer::from_seconds(WeatherKind::Clear.duration_range().0, false),
+        }
+    }
+}
+
+impl Default for Temperature {
+    fn default() -> Self {
+        Self {
+            current: BASE_TEMPERATURE,
+        }
+    }
+}
+
+impl WeatherKind {
+    /// Chances of what comes next, roll is between 0 and 1
+    pub fn next(self, roll: f32) -> WeatherKind {
+        let table: &[(WeatherKind, f32)] = match self {
+            WeatherKind::Clear => &[
+                (WeatherKind::Clear, 0.45),
+                (WeatherKind::Rain, 0.4),
+                (WeatherKind::Snow, 0.15),
+            ],
+            WeatherKind::Rain => &[
+                (WeatherKind::Clear, 0.5),
+                (WeatherKind::Rain, 0.2),
+                (WeatherKind::Storm, 0.3),
+            ],
+            WeatherKind::Storm => &[(WeatherKind::Rain, 0.7), (WeatherKind::Clear, 0.3)],
+            WeatherKind::Snow => &[(WeatherKind::Clear, 0.6), (WeatherKind::Snow, 0.4)],
+        };
+        let mut total = 0.0;
+        for (kind, chance) in table.iter() {
+            total += chance;
+            if roll < total {
+                return *kind;
+            }
+        }
+        table[table.len() - 1].0
+    }
+
+    /// Shortest and longest time in seconds this weather lasts
+    pub fn duration_range(self) -> (f32, f32) {
+        match self {
+            WeatherKind::Clear => (60.0, 180.0),
+            WeatherKind::Rain => (40.0, 120.0),
+            WeatherKind::Storm => (20.0, 60.0),
+            WeatherKind::Snow => (40.0, 120.0),
+        }
+    }
+
+    /// Strength lost by an uncovered fire every second
+    pub fn fire_drain(self) -> f32 {
+        match self {
+            WeatherKind::Clear => 0.0,
+            WeatherKind::Rain => 0.05,
+            WeatherKind::Storm => 0.15,
+            WeatherKind::Snow => 0.03,
+        }
+    }
+
+    /// How much faster plants regrow
+    pub fn growth_multiplier(self) -> f32 {
+        match self {
+            WeatherKind::Rain => 2.0,
+            WeatherKind::Storm => 1.5,
+            _ => 1.0,
+        }
+    }
+
+    /// Change to the air temperature
+    pub fn temperature_offset(self) -> f32 {
+        match self {
+            WeatherKind::Clear => 0.0,
+            WeatherKind::Rain => -4.0,
+            WeatherKind::Storm => -8.0,
+            WeatherKind::Snow => -18.0,
+        }
+    }
+
+    /// Snow only falls in winter and winter rain falls as snow
+    pub fn in_season(self, season: Season) -> WeatherKind {
+        match (self, season) {
+            (WeatherKind::Rain, Season::Winter) => WeatherKind::Snow,
+            (WeatherKind::Snow, Season::Winter) => WeatherKind::Snow,
+            (WeatherKind::Snow, _) => WeatherKind::Rain,
+            (kind, _) => kind,
+        }
+    }
+
+    /// Particles spawned per second and the direction they fall in tiles per second
+    fn particles(self) -> Option<(f32, Vec2)> {
+        match self {
+            WeatherKind::Clear => None,
+            WeatherKind::Rain => Some((120.0, Vec2::new(-1.0, -12.0))),
+            WeatherKind::Storm => Some((300.0, Vec2::new(-6.0, -14.0))),
+            WeatherKind::Snow => Some((60.0, Vec2::new(-0.5, -1.5))),
+        }
+    }
+}
+
+/// Warmth given off by a fire at a distance, nothing past the edge of its light
+pub fn fire_heat(fire: &Fire, light_radius: f32, distance: f32) -> f32 {
+    if light_radius <= 0.0 || distance >= light_radius {
+        return 0.0;
+    }
+    fire.strength * FIRE_HEAT_PER_STRENGTH * (1.0 - distance / light_radius)
+}
+
+impl WeatherPlugin {
+    fn seed_weather(mut commands: Commands, seed: Res<WorldSeed>) {
+        commands.insert_resource(WeatherRng {
+            weather: ChaCha8Rng::seed_from_u64(seed.0),
+            particles: ChaCha8Rng::seed_from_u64(seed.0.wrapping_add(1)),
+        });
+    }
+
+    fn advance_weather(
+        time: Res<Time>,
+        clock: Res<WorldClock>,
+        mut weather: ResMut<Weather>,
+        mut rng: Option<ResMut<WeatherRng>>,
+        mut messages: EventWriter<GameMessage>,
+    ) {
+        let rng = match rng.as_mut() {
+            Some(rng) => &mut rng.weather,
+            None => return,
+        };
+        weather.timer.tick(time.delta());
+        if !weather.timer.finished() {
+            return;
+        }
+
+        let next = weather.kind.next(rng.gen()).in_season(clock.season());
+        let (shortest, longest) = next.duration_range();
+        weather.timer = Timer::from_seconds(rng.gen_range(shortest..longest), false);
+        if next != weather.kind {
+            messages.send(GameMessage::info(format!(
+                "The weather turns to {:?}",
+                next
+            )));
+        }
+        weather.kind = next;
+    }
+
+    fn rain_on_fires(time: Res<Time>, weather: Res<Weather>, mut fires: Query<&mut Fire>) {
+        let drain = weather.kind.fire_drain() * time.delta_seconds();
+        if drain <= 0.0 {
+            return;
+        }
+        for mut fire in fires.iter_mut() {
+            if fire.strength > 0.0 {
+                fire.strength = (fire.strength - drain).max(0.0);
+            }
+        }
+    }
+
+    /// Bodies drift towards the air temperature, warmed by nearby fires and kept warm by clothes
+    fn update_temperature(
+        time: Res<Time>,
+        weather: Res<Weather>,
+        clock: Res<WorldClock>,
+        mut bodies: Query<(&Transform, &mut Temperature, Option<&Insulation>)>,
+        fires: Query<(&Transform, &Fire, &LightSource)>,
+    ) {
+        let air = BASE_TEMPERATURE
+            + clock.season().temperature_offset()
+            + weather.kind.temperature_offset();
+        for (transform, mut temperature, insulation) in bodies.iter_mut() {
+            let position = transform.translation.truncate();
+            let heat: f32 = fires
+                .iter()
+                .map(|(fire_transform, fire, light)| {
+                    let distance = fire_transform.translation.truncate().distance(position);
+                    fire_heat(fire, light.radius, distance)
+                })
+                .sum();
+            let insulation = insulation.map(|insulation| insulation.0).unwrap_or(0.0);
+            //Clothes keep the cold out but don't make a warm day hotter
+            let target = (air + heat).max((air + insulation).min(BASE_TEMPERATURE));
+
+            let step = TEMPERATURE_CHANGE_RATE * time.delta_seconds();
+            let change = (target - temperature.current).clamp(-step, step);
+            if change != 0.0 {
+                temperature.current += change;
+            }
+        }
+    }
+
+    fn cold_damage(time: Res<Time>, mut bodies: Query<(&Temperature, &mut Health)>) {
+        for (temperature, mut health) in bodies.iter_mut() {
+            let cold = COLD_THRESHOLD - temperature.current;
+            if cold > 0.0 && !health.is_dead() {
+                health.damage(cold * COLD_DAMAGE_PER_DEGREE * time.delta_seconds());
+            }
+        }
+    }
+
+    /// The cold slows bodies down for as long as it lasts, rain leaves them wet for a while
+    fn weather_slowdown(
+        weather: Res<Weather>,
+        mut bodies: Query<(&Temperature, &mut SpeedModifiers)>,
+    ) {
+        let raining = matches!(weather.kind, WeatherKind::Rain | WeatherKind::Storm);
+        for (temperature, mut modifiers) in bodies.iter_mut() {
+            let cold = COLD_THRESHOLD - temperature.current;
+            if cold > 0.0 {
+                let speed = (1.0 - cold * COLD_SLOWDOWN_PER_DEGREE).max(MAX_COLD_SLOWDOWN);
+                if modifiers
+                    .effect(SpeedSource::Cold)
+                    .map(|effect| effect.multiplier)
+                    != Some(speed)
+                {
+                    modifiers.set_effect(SpeedSource::Cold, speed, None);
+                }
+            } else if modifiers.effect(SpeedSource::Cold).is_some() {
+                modifiers.remove_effect(SpeedSource::Cold);
+            }
+            //Restarted every frame in the rain so drying only starts once it stops
+            if raining {
+                modifiers.set_effect(SpeedSource::Wet, WET_SPEED, Some(DRYING_SECONDS));
+            }
+        }
+    }
+
+    /// Rain and snow fall across the camera view
+    fn spawn_particles(
+        mut commands: Commands,
+        time: Res<Time>,
+        weather: Res<Weather>,
+        mut rng: Option<ResMut<WeatherRng>>,
+        camera: Query<(&Transform, &OrthographicProjection), With<Camera2d>>,
+    ) {
+        let rng = match rng.as_mut() {
+            Some(rng) => &mut rng.particles,
+            None => return,
+        };
+        let (rate, velocity) = match weather.kind.particles() {
+            Some(particles) => particles,
+            None => return,
+        };
+        let (camera_transform, projection) = match camera.get_single() {
+            Ok(camera) => camera,
+            Err(_) => return,
+        };
+
+        let centre = camera_transform.translation.truncate();
+        let min = centre + Vec2::new(projection.left, projection.bottom) * projection.scale;
+        let max = centre + Vec2::new(projection.right, projection.top) * projection.scale;
+        //Particles start above the view and live long enough to fall through it
+        let lifetime = (max.y - min.y) / -velocity.y;
+
+        let (color, size) = match weather.kind {
+            WeatherKind::Snow => (Color::rgba(1.0, 1.0, 1.0, 0.9), Vec2::splat(0.08)),
+            _ => (Color::rgba(0.6, 0.7, 1.0, 0.6), Vec2::new(0.02, 0.3)),
+        };
+
+        let count = rate * time.delta_seconds() + rng.gen::<f32>();
+        for _ in 0..count as usize {
+            let x = rng.gen_range(min.x..max.x - velocity.x * lifetime);
+            let y = rng.gen_range(max.y..max.y + 1.0);
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(size),
+                        ..Default::default()
+                    },
+                    transform: Transform {
+                        translation: Vec3::new(x, y, PARTICLE_LAYER),
+                        //Rain streaks lean with the wind
+                        rotation: Quat::from_rotation_z(velocity.x.atan2(-velocity.y)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(WeatherParticle {
+                    velocity: velocity * rng.gen_range(0.8..1.2),
+                    lifetime: Timer::from_seconds(lifetime, false),
+                });
+        }
+    }
+
+    fn move_particles(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut particles: Query<(Entity, &mut Transform, &mut WeatherParticle)>,
+    ) {
+        for (entity, mut transform, mut particle) in particles.iter_mut() {
+            particle.lifetime.tick(time.delta());
+            if particle.lifetime.finished() {
+                commands.entity(entity).despawn();
+                continue;
+            }
+            transform.translation += (particle.velocity * time.delta_seconds()).extend(0.0);
+        }
+    }
+}